@@ -1,3 +1,4 @@
 #!/bin/bash
 
-rust_elm_typegen -i src/pomp.rs -o generated/PompData.elm -m PompData
\ No newline at end of file
+rust_elm_typegen -i src/pomp.rs -o generated/PompData.elm -m PompData
+rust_elm_typegen -i src/wedding_types.rs -o generated/WeddingData.elm -m WeddingData
@@ -0,0 +1,622 @@
+module PompData exposing (..)
+
+
+import Json.Decode
+import Json.Encode
+import Json.Decode.Pipeline
+
+type alias PompPlayerView =
+    { myInventory : (Maybe PlayerInventoryView)
+    , myReserved : (List Card)
+    , others : (List PlayerInventoryView)
+    , market : (List (List (Maybe Card)))
+    , deckSizes : (List Int)
+    , patrons : (List Patron)
+    , gamble : GambleView
+    , secondsLeft : (Maybe Int)
+    , ranking : (List Rank)
+    , seed : Int
+    }
+
+encodePompPlayerView : PompPlayerView -> Json.Encode.Value
+encodePompPlayerView pompplayerview =
+    Json.Encode.object
+        [ ( "my_inventory", (Maybe.map encodePlayerInventoryView >> Maybe.withDefault Json.Encode.null) pompplayerview.myInventory )
+        , ( "my_reserved", (Json.Encode.list encodeCard) pompplayerview.myReserved )
+        , ( "others", (Json.Encode.list encodePlayerInventoryView) pompplayerview.others )
+        , ( "market", (Json.Encode.list (Json.Encode.list (Maybe.map encodeCard >> Maybe.withDefault Json.Encode.null))) pompplayerview.market )
+        , ( "deck_sizes", (Json.Encode.list Json.Encode.int) pompplayerview.deckSizes )
+        , ( "patrons", (Json.Encode.list encodePatron) pompplayerview.patrons )
+        , ( "gamble", encodeGambleView pompplayerview.gamble )
+        , ( "seconds_left", (Maybe.map Json.Encode.int >> Maybe.withDefault Json.Encode.null) pompplayerview.secondsLeft )
+        , ( "ranking", (Json.Encode.list encodeRank) pompplayerview.ranking )
+        , ( "seed", Json.Encode.int pompplayerview.seed )
+        ]
+
+decodePompPlayerView : Json.Decode.Decoder PompPlayerView
+decodePompPlayerView =
+    Json.Decode.succeed PompPlayerView
+        |> Json.Decode.Pipeline.required "my_inventory" (Json.Decode.nullable decodePlayerInventoryView)
+        |> Json.Decode.Pipeline.required "my_reserved" (Json.Decode.list decodeCard)
+        |> Json.Decode.Pipeline.required "others" (Json.Decode.list decodePlayerInventoryView)
+        |> Json.Decode.Pipeline.required "market" (Json.Decode.list (Json.Decode.list (Json.Decode.nullable decodeCard)))
+        |> Json.Decode.Pipeline.required "deck_sizes" (Json.Decode.list Json.Decode.int)
+        |> Json.Decode.Pipeline.required "patrons" (Json.Decode.list decodePatron)
+        |> Json.Decode.Pipeline.required "gamble" decodeGambleView
+        |> Json.Decode.Pipeline.required "seconds_left" (Json.Decode.nullable Json.Decode.int)
+        |> Json.Decode.Pipeline.required "ranking" (Json.Decode.list decodeRank)
+        |> Json.Decode.Pipeline.required "seed" Json.Decode.int
+
+type alias PlayerInventoryView =
+    { name : String
+    , away : Bool
+    , points : Int
+    , energy : Int
+    , energyPerMinute : Int
+    , elements : ElementVector
+    , discount : ElementVector
+    , income : ElementVector
+    , reservedTiers : (List Int)
+    }
+
+encodePlayerInventoryView : PlayerInventoryView -> Json.Encode.Value
+encodePlayerInventoryView playerinventoryview =
+    Json.Encode.object
+        [ ( "name", Json.Encode.string playerinventoryview.name )
+        , ( "away", Json.Encode.bool playerinventoryview.away )
+        , ( "points", Json.Encode.int playerinventoryview.points )
+        , ( "energy", Json.Encode.int playerinventoryview.energy )
+        , ( "energy_per_minute", Json.Encode.int playerinventoryview.energyPerMinute )
+        , ( "elements", encodeElementVector playerinventoryview.elements )
+        , ( "discount", encodeElementVector playerinventoryview.discount )
+        , ( "income", encodeElementVector playerinventoryview.income )
+        , ( "reserved_tiers", (Json.Encode.list Json.Encode.int) playerinventoryview.reservedTiers )
+        ]
+
+decodePlayerInventoryView : Json.Decode.Decoder PlayerInventoryView
+decodePlayerInventoryView =
+    Json.Decode.succeed PlayerInventoryView
+        |> Json.Decode.Pipeline.required "name" Json.Decode.string
+        |> Json.Decode.Pipeline.required "away" Json.Decode.bool
+        |> Json.Decode.Pipeline.required "points" Json.Decode.int
+        |> Json.Decode.Pipeline.required "energy" Json.Decode.int
+        |> Json.Decode.Pipeline.required "energy_per_minute" Json.Decode.int
+        |> Json.Decode.Pipeline.required "elements" decodeElementVector
+        |> Json.Decode.Pipeline.required "discount" decodeElementVector
+        |> Json.Decode.Pipeline.required "income" decodeElementVector
+        |> Json.Decode.Pipeline.required "reserved_tiers" (Json.Decode.list Json.Decode.int)
+
+type alias ElementVector =
+    { fire : Int
+    , plant : Int
+    , water : Int
+    , earth : Int
+    , chaos : Int
+    }
+
+encodeElementVector : ElementVector -> Json.Encode.Value
+encodeElementVector elementvector =
+    Json.Encode.object
+        [ ( "fire", Json.Encode.int elementvector.fire )
+        , ( "plant", Json.Encode.int elementvector.plant )
+        , ( "water", Json.Encode.int elementvector.water )
+        , ( "earth", Json.Encode.int elementvector.earth )
+        , ( "chaos", Json.Encode.int elementvector.chaos )
+        ]
+
+decodeElementVector : Json.Decode.Decoder ElementVector
+decodeElementVector =
+    Json.Decode.succeed ElementVector
+        |> Json.Decode.Pipeline.required "fire" Json.Decode.int
+        |> Json.Decode.Pipeline.required "plant" Json.Decode.int
+        |> Json.Decode.Pipeline.required "water" Json.Decode.int
+        |> Json.Decode.Pipeline.required "earth" Json.Decode.int
+        |> Json.Decode.Pipeline.required "chaos" Json.Decode.int
+
+type alias Card =
+    { id : Int
+    , color : ElementColor
+    , points : Int
+    , cost : ElementVector
+    , power : (Maybe Power)
+    }
+
+encodeCard : Card -> Json.Encode.Value
+encodeCard card =
+    Json.Encode.object
+        [ ( "id", Json.Encode.int card.id )
+        , ( "color", encodeElementColor card.color )
+        , ( "points", Json.Encode.int card.points )
+        , ( "cost", encodeElementVector card.cost )
+        , ( "power", (Maybe.map encodePower >> Maybe.withDefault Json.Encode.null) card.power )
+        ]
+
+decodeCard : Json.Decode.Decoder Card
+decodeCard =
+    Json.Decode.succeed Card
+        |> Json.Decode.Pipeline.required "id" Json.Decode.int
+        |> Json.Decode.Pipeline.required "color" decodeElementColor
+        |> Json.Decode.Pipeline.required "points" Json.Decode.int
+        |> Json.Decode.Pipeline.required "cost" decodeElementVector
+        |> Json.Decode.Pipeline.required "power" (Json.Decode.nullable decodePower)
+
+type alias GambleView =
+    { size : Int
+    , price : Int
+    }
+
+encodeGambleView : GambleView -> Json.Encode.Value
+encodeGambleView gambleview =
+    Json.Encode.object
+        [ ( "size", Json.Encode.int gambleview.size )
+        , ( "price", Json.Encode.int gambleview.price )
+        ]
+
+decodeGambleView : Json.Decode.Decoder GambleView
+decodeGambleView =
+    Json.Decode.succeed GambleView
+        |> Json.Decode.Pipeline.required "size" Json.Decode.int
+        |> Json.Decode.Pipeline.required "price" Json.Decode.int
+
+type alias Power =
+    { effect : PowerEffect
+    , minCost : Int
+    }
+
+encodePower : Power -> Json.Encode.Value
+encodePower power =
+    Json.Encode.object
+        [ ( "effect", encodePowerEffect power.effect )
+        , ( "min_cost", Json.Encode.int power.minCost )
+        ]
+
+decodePower : Json.Decode.Decoder Power
+decodePower =
+    Json.Decode.succeed Power
+        |> Json.Decode.Pipeline.required "effect" decodePowerEffect
+        |> Json.Decode.Pipeline.required "min_cost" Json.Decode.int
+
+type alias Patron =
+    { id : Int
+    , requirement : ElementVector
+    , points : Int
+    , owner : (Maybe String)
+    }
+
+encodePatron : Patron -> Json.Encode.Value
+encodePatron patron =
+    Json.Encode.object
+        [ ( "id", Json.Encode.int patron.id )
+        , ( "requirement", encodeElementVector patron.requirement )
+        , ( "points", Json.Encode.int patron.points )
+        , ( "owner", (Maybe.map Json.Encode.string >> Maybe.withDefault Json.Encode.null) patron.owner )
+        ]
+
+decodePatron : Json.Decode.Decoder Patron
+decodePatron =
+    Json.Decode.succeed Patron
+        |> Json.Decode.Pipeline.required "id" Json.Decode.int
+        |> Json.Decode.Pipeline.required "requirement" decodeElementVector
+        |> Json.Decode.Pipeline.required "points" Json.Decode.int
+        |> Json.Decode.Pipeline.required "owner" (Json.Decode.nullable Json.Decode.string)
+
+type alias Rank =
+    { place : Int
+    , name : String
+    , points : Int
+    , cards : Int
+    , elements : Int
+    }
+
+encodeRank : Rank -> Json.Encode.Value
+encodeRank rank =
+    Json.Encode.object
+        [ ( "place", Json.Encode.int rank.place )
+        , ( "name", Json.Encode.string rank.name )
+        , ( "points", Json.Encode.int rank.points )
+        , ( "cards", Json.Encode.int rank.cards )
+        , ( "elements", Json.Encode.int rank.elements )
+        ]
+
+decodeRank : Json.Decode.Decoder Rank
+decodeRank =
+    Json.Decode.succeed Rank
+        |> Json.Decode.Pipeline.required "place" Json.Decode.int
+        |> Json.Decode.Pipeline.required "name" Json.Decode.string
+        |> Json.Decode.Pipeline.required "points" Json.Decode.int
+        |> Json.Decode.Pipeline.required "cards" Json.Decode.int
+        |> Json.Decode.Pipeline.required "elements" Json.Decode.int
+
+type ElementColor
+    = Fire
+    | Plant
+    | Water
+    | Earth
+    | Chaos
+
+encodeElementColor : ElementColor -> Json.Encode.Value
+encodeElementColor elementcolor =
+    case elementcolor of
+        Fire ->
+            Json.Encode.string "Fire"
+
+        Plant ->
+            Json.Encode.string "Plant"
+
+        Water ->
+            Json.Encode.string "Water"
+
+        Earth ->
+            Json.Encode.string "Earth"
+
+        Chaos ->
+            Json.Encode.string "Chaos"
+
+
+decodeElementColor : Json.Decode.Decoder ElementColor
+decodeElementColor =
+    Json.Decode.oneOf
+        [ decodeElementColorFire
+        , decodeElementColorPlant
+        , decodeElementColorWater
+        , decodeElementColorEarth
+        , decodeElementColorChaos
+        ]
+
+
+decodeElementColorFire : Json.Decode.Decoder ElementColor
+decodeElementColorFire =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Fire" ->
+                    Json.Decode.succeed Fire
+
+                _ ->
+                    Json.Decode.fail "Expected variant Fire"
+        )
+        Json.Decode.string
+
+
+decodeElementColorPlant : Json.Decode.Decoder ElementColor
+decodeElementColorPlant =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Plant" ->
+                    Json.Decode.succeed Plant
+
+                _ ->
+                    Json.Decode.fail "Expected variant Plant"
+        )
+        Json.Decode.string
+
+
+decodeElementColorWater : Json.Decode.Decoder ElementColor
+decodeElementColorWater =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Water" ->
+                    Json.Decode.succeed Water
+
+                _ ->
+                    Json.Decode.fail "Expected variant Water"
+        )
+        Json.Decode.string
+
+
+decodeElementColorEarth : Json.Decode.Decoder ElementColor
+decodeElementColorEarth =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Earth" ->
+                    Json.Decode.succeed Earth
+
+                _ ->
+                    Json.Decode.fail "Expected variant Earth"
+        )
+        Json.Decode.string
+
+
+decodeElementColorChaos : Json.Decode.Decoder ElementColor
+decodeElementColorChaos =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Chaos" ->
+                    Json.Decode.succeed Chaos
+
+                _ ->
+                    Json.Decode.fail "Expected variant Chaos"
+        )
+        Json.Decode.string
+
+type PompEvent
+    = Buy ElementColor
+    | BuyCard Int
+    | Reserve Int
+    | BuyReserved Int
+    | Gamble
+
+encodePompEvent : PompEvent -> Json.Encode.Value
+encodePompEvent pompevent =
+    case pompevent of
+        Buy x ->
+            Json.Encode.object
+                [ ( "Buy", encodeElementColor x )
+                ]
+
+        BuyCard x ->
+            Json.Encode.object
+                [ ( "BuyCard", Json.Encode.int x )
+                ]
+
+        Reserve x ->
+            Json.Encode.object
+                [ ( "Reserve", Json.Encode.int x )
+                ]
+
+        BuyReserved x ->
+            Json.Encode.object
+                [ ( "BuyReserved", Json.Encode.int x )
+                ]
+
+        Gamble ->
+            Json.Encode.string "Gamble"
+
+
+decodePompEvent : Json.Decode.Decoder PompEvent
+decodePompEvent =
+    Json.Decode.oneOf
+        [ decodePompEventBuy
+        , decodePompEventBuyCard
+        , decodePompEventReserve
+        , decodePompEventBuyReserved
+        , decodePompEventGamble
+        ]
+
+
+decodePompEventBuy : Json.Decode.Decoder PompEvent
+decodePompEventBuy =
+    Json.Decode.succeed Buy
+        |> Json.Decode.Pipeline.required "Buy" decodeElementColor
+
+
+decodePompEventBuyCard : Json.Decode.Decoder PompEvent
+decodePompEventBuyCard =
+    Json.Decode.succeed BuyCard
+        |> Json.Decode.Pipeline.required "BuyCard" Json.Decode.int
+
+
+decodePompEventReserve : Json.Decode.Decoder PompEvent
+decodePompEventReserve =
+    Json.Decode.succeed Reserve
+        |> Json.Decode.Pipeline.required "Reserve" Json.Decode.int
+
+
+decodePompEventBuyReserved : Json.Decode.Decoder PompEvent
+decodePompEventBuyReserved =
+    Json.Decode.succeed BuyReserved
+        |> Json.Decode.Pipeline.required "BuyReserved" Json.Decode.int
+
+
+decodePompEventGamble : Json.Decode.Decoder PompEvent
+decodePompEventGamble =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Gamble" ->
+                    Json.Decode.succeed Gamble
+
+                _ ->
+                    Json.Decode.fail "Expected variant Gamble"
+        )
+        Json.Decode.string
+
+type PompRejection
+    = GameOver
+    | NotAPlayer
+    | NotEnoughEnergy
+    | ChaosNotForSale
+    | CardNotOnMarket
+    | CardNotReserved
+    | ReserveFull
+    | NotEnoughElements
+
+encodePompRejection : PompRejection -> Json.Encode.Value
+encodePompRejection pomprejection =
+    case pomprejection of
+        GameOver ->
+            Json.Encode.string "GameOver"
+
+        NotAPlayer ->
+            Json.Encode.string "NotAPlayer"
+
+        NotEnoughEnergy ->
+            Json.Encode.string "NotEnoughEnergy"
+
+        ChaosNotForSale ->
+            Json.Encode.string "ChaosNotForSale"
+
+        CardNotOnMarket ->
+            Json.Encode.string "CardNotOnMarket"
+
+        CardNotReserved ->
+            Json.Encode.string "CardNotReserved"
+
+        ReserveFull ->
+            Json.Encode.string "ReserveFull"
+
+        NotEnoughElements ->
+            Json.Encode.string "NotEnoughElements"
+
+
+decodePompRejection : Json.Decode.Decoder PompRejection
+decodePompRejection =
+    Json.Decode.oneOf
+        [ decodePompRejectionGameOver
+        , decodePompRejectionNotAPlayer
+        , decodePompRejectionNotEnoughEnergy
+        , decodePompRejectionChaosNotForSale
+        , decodePompRejectionCardNotOnMarket
+        , decodePompRejectionCardNotReserved
+        , decodePompRejectionReserveFull
+        , decodePompRejectionNotEnoughElements
+        ]
+
+
+decodePompRejectionGameOver : Json.Decode.Decoder PompRejection
+decodePompRejectionGameOver =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "GameOver" ->
+                    Json.Decode.succeed GameOver
+
+                _ ->
+                    Json.Decode.fail "Expected variant GameOver"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionNotAPlayer : Json.Decode.Decoder PompRejection
+decodePompRejectionNotAPlayer =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "NotAPlayer" ->
+                    Json.Decode.succeed NotAPlayer
+
+                _ ->
+                    Json.Decode.fail "Expected variant NotAPlayer"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionNotEnoughEnergy : Json.Decode.Decoder PompRejection
+decodePompRejectionNotEnoughEnergy =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "NotEnoughEnergy" ->
+                    Json.Decode.succeed NotEnoughEnergy
+
+                _ ->
+                    Json.Decode.fail "Expected variant NotEnoughEnergy"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionChaosNotForSale : Json.Decode.Decoder PompRejection
+decodePompRejectionChaosNotForSale =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "ChaosNotForSale" ->
+                    Json.Decode.succeed ChaosNotForSale
+
+                _ ->
+                    Json.Decode.fail "Expected variant ChaosNotForSale"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionCardNotOnMarket : Json.Decode.Decoder PompRejection
+decodePompRejectionCardNotOnMarket =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "CardNotOnMarket" ->
+                    Json.Decode.succeed CardNotOnMarket
+
+                _ ->
+                    Json.Decode.fail "Expected variant CardNotOnMarket"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionCardNotReserved : Json.Decode.Decoder PompRejection
+decodePompRejectionCardNotReserved =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "CardNotReserved" ->
+                    Json.Decode.succeed CardNotReserved
+
+                _ ->
+                    Json.Decode.fail "Expected variant CardNotReserved"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionReserveFull : Json.Decode.Decoder PompRejection
+decodePompRejectionReserveFull =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "ReserveFull" ->
+                    Json.Decode.succeed ReserveFull
+
+                _ ->
+                    Json.Decode.fail "Expected variant ReserveFull"
+        )
+        Json.Decode.string
+
+
+decodePompRejectionNotEnoughElements : Json.Decode.Decoder PompRejection
+decodePompRejectionNotEnoughElements =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "NotEnoughElements" ->
+                    Json.Decode.succeed NotEnoughElements
+
+                _ ->
+                    Json.Decode.fail "Expected variant NotEnoughElements"
+        )
+        Json.Decode.string
+
+type PowerEffect
+    = FasterEnergy
+    | ElementIncome ElementColor
+
+encodePowerEffect : PowerEffect -> Json.Encode.Value
+encodePowerEffect powereffect =
+    case powereffect of
+        FasterEnergy ->
+            Json.Encode.string "FasterEnergy"
+
+        ElementIncome x ->
+            Json.Encode.object
+                [ ( "ElementIncome", encodeElementColor x )
+                ]
+
+
+decodePowerEffect : Json.Decode.Decoder PowerEffect
+decodePowerEffect =
+    Json.Decode.oneOf
+        [ decodePowerEffectFasterEnergy
+        , decodePowerEffectElementIncome
+        ]
+
+
+decodePowerEffectFasterEnergy : Json.Decode.Decoder PowerEffect
+decodePowerEffectFasterEnergy =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "FasterEnergy" ->
+                    Json.Decode.succeed FasterEnergy
+
+                _ ->
+                    Json.Decode.fail "Expected variant FasterEnergy"
+        )
+        Json.Decode.string
+
+
+decodePowerEffectElementIncome : Json.Decode.Decoder PowerEffect
+decodePowerEffectElementIncome =
+    Json.Decode.succeed ElementIncome
+        |> Json.Decode.Pipeline.required "ElementIncome" decodeElementColor
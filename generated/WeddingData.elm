@@ -0,0 +1,487 @@
+module WeddingData exposing (..)
+
+
+import Json.Decode
+import Json.Encode
+import Json.Decode.Pipeline
+
+type alias QuestionView =
+    { text : String
+    , state : QuestionState
+    , brideGuesses : Int
+    , groomGuesses : Int
+    }
+
+encodeQuestionView : QuestionView -> Json.Encode.Value
+encodeQuestionView questionview =
+    Json.Encode.object
+        [ ( "text", Json.Encode.string questionview.text )
+        , ( "state", encodeQuestionState questionview.state )
+        , ( "bride_guesses", Json.Encode.int questionview.brideGuesses )
+        , ( "groom_guesses", Json.Encode.int questionview.groomGuesses )
+        ]
+
+decodeQuestionView : Json.Decode.Decoder QuestionView
+decodeQuestionView =
+    Json.Decode.succeed QuestionView
+        |> Json.Decode.Pipeline.required "text" Json.Decode.string
+        |> Json.Decode.Pipeline.required "state" decodeQuestionState
+        |> Json.Decode.Pipeline.required "bride_guesses" Json.Decode.int
+        |> Json.Decode.Pipeline.required "groom_guesses" Json.Decode.int
+
+type alias GuestView =
+    { name : String
+    , question : String
+    , guess : (Maybe Espoused)
+    , state : QuestionState
+    , score : Int
+    }
+
+encodeGuestView : GuestView -> Json.Encode.Value
+encodeGuestView guestview =
+    Json.Encode.object
+        [ ( "name", Json.Encode.string guestview.name )
+        , ( "question", Json.Encode.string guestview.question )
+        , ( "guess", (Maybe.map encodeEspoused >> Maybe.withDefault Json.Encode.null) guestview.guess )
+        , ( "state", encodeQuestionState guestview.state )
+        , ( "score", Json.Encode.int guestview.score )
+        ]
+
+decodeGuestView : Json.Decode.Decoder GuestView
+decodeGuestView =
+    Json.Decode.succeed GuestView
+        |> Json.Decode.Pipeline.required "name" Json.Decode.string
+        |> Json.Decode.Pipeline.required "question" Json.Decode.string
+        |> Json.Decode.Pipeline.required "guess" (Json.Decode.nullable decodeEspoused)
+        |> Json.Decode.Pipeline.required "state" decodeQuestionState
+        |> Json.Decode.Pipeline.required "score" Json.Decode.int
+
+type alias HostView =
+    { questions : (List QuestionView)
+    , currentQuestion : (Maybe Int)
+    }
+
+encodeHostView : HostView -> Json.Encode.Value
+encodeHostView hostview =
+    Json.Encode.object
+        [ ( "questions", (Json.Encode.list encodeQuestionView) hostview.questions )
+        , ( "current_question", (Maybe.map Json.Encode.int >> Maybe.withDefault Json.Encode.null) hostview.currentQuestion )
+        ]
+
+decodeHostView : Json.Decode.Decoder HostView
+decodeHostView =
+    Json.Decode.succeed HostView
+        |> Json.Decode.Pipeline.required "questions" (Json.Decode.list decodeQuestionView)
+        |> Json.Decode.Pipeline.required "current_question" (Json.Decode.nullable Json.Decode.int)
+
+type alias ProjectorView =
+    { question : (Maybe QuestionView)
+    , connectedUsers : (List String)
+    , highScores : (List HighScoreEntry)
+    , currentQuestionHighScores : (List HighScoreEntry)
+    }
+
+encodeProjectorView : ProjectorView -> Json.Encode.Value
+encodeProjectorView projectorview =
+    Json.Encode.object
+        [ ( "question", (Maybe.map encodeQuestionView >> Maybe.withDefault Json.Encode.null) projectorview.question )
+        , ( "connected_users", (Json.Encode.list Json.Encode.string) projectorview.connectedUsers )
+        , ( "high_scores", (Json.Encode.list encodeHighScoreEntry) projectorview.highScores )
+        , ( "current_question_high_scores", (Json.Encode.list encodeHighScoreEntry) projectorview.currentQuestionHighScores )
+        ]
+
+decodeProjectorView : Json.Decode.Decoder ProjectorView
+decodeProjectorView =
+    Json.Decode.succeed ProjectorView
+        |> Json.Decode.Pipeline.required "question" (Json.Decode.nullable decodeQuestionView)
+        |> Json.Decode.Pipeline.required "connected_users" (Json.Decode.list Json.Decode.string)
+        |> Json.Decode.Pipeline.required "high_scores" (Json.Decode.list decodeHighScoreEntry)
+        |> Json.Decode.Pipeline.required "current_question_high_scores" (Json.Decode.list decodeHighScoreEntry)
+
+type alias HighScoreEntry =
+    { name : String
+    , score : Int
+    }
+
+encodeHighScoreEntry : HighScoreEntry -> Json.Encode.Value
+encodeHighScoreEntry highscoreentry =
+    Json.Encode.object
+        [ ( "name", Json.Encode.string highscoreentry.name )
+        , ( "score", Json.Encode.int highscoreentry.score )
+        ]
+
+decodeHighScoreEntry : Json.Decode.Decoder HighScoreEntry
+decodeHighScoreEntry =
+    Json.Decode.succeed HighScoreEntry
+        |> Json.Decode.Pipeline.required "name" Json.Decode.string
+        |> Json.Decode.Pipeline.required "score" Json.Decode.int
+
+type Espoused
+    = Bride
+    | Groom
+
+encodeEspoused : Espoused -> Json.Encode.Value
+encodeEspoused espoused =
+    case espoused of
+        Bride ->
+            Json.Encode.string "Bride"
+
+        Groom ->
+            Json.Encode.string "Groom"
+
+
+decodeEspoused : Json.Decode.Decoder Espoused
+decodeEspoused =
+    Json.Decode.oneOf
+        [ decodeEspousedBride
+        , decodeEspousedGroom
+        ]
+
+
+decodeEspousedBride : Json.Decode.Decoder Espoused
+decodeEspousedBride =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Bride" ->
+                    Json.Decode.succeed Bride
+
+                _ ->
+                    Json.Decode.fail "Expected variant Bride"
+        )
+        Json.Decode.string
+
+
+decodeEspousedGroom : Json.Decode.Decoder Espoused
+decodeEspousedGroom =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "Groom" ->
+                    Json.Decode.succeed Groom
+
+                _ ->
+                    Json.Decode.fail "Expected variant Groom"
+        )
+        Json.Decode.string
+
+type QuestionState
+    = GuestsCanVote
+    | VotingClosed
+    | Answered Espoused
+    | ConflictAnswer
+
+encodeQuestionState : QuestionState -> Json.Encode.Value
+encodeQuestionState questionstate =
+    case questionstate of
+        GuestsCanVote ->
+            Json.Encode.string "GuestsCanVote"
+
+        VotingClosed ->
+            Json.Encode.string "VotingClosed"
+
+        Answered x ->
+            Json.Encode.object
+                [ ( "Answered", encodeEspoused x )
+                ]
+
+        ConflictAnswer ->
+            Json.Encode.string "ConflictAnswer"
+
+
+decodeQuestionState : Json.Decode.Decoder QuestionState
+decodeQuestionState =
+    Json.Decode.oneOf
+        [ decodeQuestionStateGuestsCanVote
+        , decodeQuestionStateVotingClosed
+        , decodeQuestionStateAnswered
+        , decodeQuestionStateConflictAnswer
+        ]
+
+
+decodeQuestionStateGuestsCanVote : Json.Decode.Decoder QuestionState
+decodeQuestionStateGuestsCanVote =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "GuestsCanVote" ->
+                    Json.Decode.succeed GuestsCanVote
+
+                _ ->
+                    Json.Decode.fail "Expected variant GuestsCanVote"
+        )
+        Json.Decode.string
+
+
+decodeQuestionStateVotingClosed : Json.Decode.Decoder QuestionState
+decodeQuestionStateVotingClosed =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "VotingClosed" ->
+                    Json.Decode.succeed VotingClosed
+
+                _ ->
+                    Json.Decode.fail "Expected variant VotingClosed"
+        )
+        Json.Decode.string
+
+
+decodeQuestionStateAnswered : Json.Decode.Decoder QuestionState
+decodeQuestionStateAnswered =
+    Json.Decode.succeed Answered
+        |> Json.Decode.Pipeline.required "Answered" decodeEspoused
+
+
+decodeQuestionStateConflictAnswer : Json.Decode.Decoder QuestionState
+decodeQuestionStateConflictAnswer =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "ConflictAnswer" ->
+                    Json.Decode.succeed ConflictAnswer
+
+                _ ->
+                    Json.Decode.fail "Expected variant ConflictAnswer"
+        )
+        Json.Decode.string
+
+type WeddingView
+    = SignUp
+    | Guest GuestView
+    | Host HostView
+    | Projector ProjectorView
+
+encodeWeddingView : WeddingView -> Json.Encode.Value
+encodeWeddingView weddingview =
+    case weddingview of
+        SignUp ->
+            Json.Encode.string "SignUp"
+
+        Guest x ->
+            Json.Encode.object
+                [ ( "Guest", encodeGuestView x )
+                ]
+
+        Host x ->
+            Json.Encode.object
+                [ ( "Host", encodeHostView x )
+                ]
+
+        Projector x ->
+            Json.Encode.object
+                [ ( "Projector", encodeProjectorView x )
+                ]
+
+
+decodeWeddingView : Json.Decode.Decoder WeddingView
+decodeWeddingView =
+    Json.Decode.oneOf
+        [ decodeWeddingViewSignUp
+        , decodeWeddingViewGuest
+        , decodeWeddingViewHost
+        , decodeWeddingViewProjector
+        ]
+
+
+decodeWeddingViewSignUp : Json.Decode.Decoder WeddingView
+decodeWeddingViewSignUp =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "SignUp" ->
+                    Json.Decode.succeed SignUp
+
+                _ ->
+                    Json.Decode.fail "Expected variant SignUp"
+        )
+        Json.Decode.string
+
+
+decodeWeddingViewGuest : Json.Decode.Decoder WeddingView
+decodeWeddingViewGuest =
+    Json.Decode.succeed Guest
+        |> Json.Decode.Pipeline.required "Guest" decodeGuestView
+
+
+decodeWeddingViewHost : Json.Decode.Decoder WeddingView
+decodeWeddingViewHost =
+    Json.Decode.succeed Host
+        |> Json.Decode.Pipeline.required "Host" decodeHostView
+
+
+decodeWeddingViewProjector : Json.Decode.Decoder WeddingView
+decodeWeddingViewProjector =
+    Json.Decode.succeed Projector
+        |> Json.Decode.Pipeline.required "Projector" decodeProjectorView
+
+type WeddingEvent
+    = SetName String
+    | RemoveName
+    | SetGuess Espoused
+    | SetQuestion (Maybe Int)
+    | SetQuestionState Int QuestionState
+    | CloseVotingLater Int
+
+encodeWeddingEvent : WeddingEvent -> Json.Encode.Value
+encodeWeddingEvent weddingevent =
+    case weddingevent of
+        SetName x ->
+            Json.Encode.object
+                [ ( "SetName", Json.Encode.string x )
+                ]
+
+        RemoveName ->
+            Json.Encode.string "RemoveName"
+
+        SetGuess x ->
+            Json.Encode.object
+                [ ( "SetGuess", encodeEspoused x )
+                ]
+
+        SetQuestion x ->
+            Json.Encode.object
+                [ ( "SetQuestion", (Maybe.map Json.Encode.int >> Maybe.withDefault Json.Encode.null) x )
+                ]
+
+        SetQuestionState x0 x1 ->
+            Json.Encode.object
+                [ ( "SetQuestionState"
+                  , Json.Encode.list (\v -> v)
+                        [ Json.Encode.int x0
+                        , encodeQuestionState x1
+                        ]
+                  )
+                ]
+
+        CloseVotingLater x ->
+            Json.Encode.object
+                [ ( "CloseVotingLater", Json.Encode.int x )
+                ]
+
+
+decodeWeddingEvent : Json.Decode.Decoder WeddingEvent
+decodeWeddingEvent =
+    Json.Decode.oneOf
+        [ decodeWeddingEventSetName
+        , decodeWeddingEventRemoveName
+        , decodeWeddingEventSetGuess
+        , decodeWeddingEventSetQuestion
+        , decodeWeddingEventSetQuestionState
+        , decodeWeddingEventCloseVotingLater
+        ]
+
+
+decodeWeddingEventSetName : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventSetName =
+    Json.Decode.succeed SetName
+        |> Json.Decode.Pipeline.required "SetName" Json.Decode.string
+
+
+decodeWeddingEventRemoveName : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventRemoveName =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "RemoveName" ->
+                    Json.Decode.succeed RemoveName
+
+                _ ->
+                    Json.Decode.fail "Expected variant RemoveName"
+        )
+        Json.Decode.string
+
+
+decodeWeddingEventSetGuess : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventSetGuess =
+    Json.Decode.succeed SetGuess
+        |> Json.Decode.Pipeline.required "SetGuess" decodeEspoused
+
+
+decodeWeddingEventSetQuestion : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventSetQuestion =
+    Json.Decode.succeed SetQuestion
+        |> Json.Decode.Pipeline.required "SetQuestion" (Json.Decode.nullable Json.Decode.int)
+
+
+decodeWeddingEventSetQuestionState : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventSetQuestionState =
+    Json.Decode.succeed SetQuestionState
+        |> Json.Decode.Pipeline.custom 
+            (Json.Decode.field "SetQuestionState" (Json.Decode.index 0 Json.Decode.int))
+        |> Json.Decode.Pipeline.custom 
+            (Json.Decode.field "SetQuestionState" (Json.Decode.index 1 decodeQuestionState))
+
+
+decodeWeddingEventCloseVotingLater : Json.Decode.Decoder WeddingEvent
+decodeWeddingEventCloseVotingLater =
+    Json.Decode.succeed CloseVotingLater
+        |> Json.Decode.Pipeline.required "CloseVotingLater" Json.Decode.int
+
+type WeddingRejection
+    = NoOpenQuestion
+    | VotingClosed
+    | UnknownQuestion
+
+encodeWeddingRejection : WeddingRejection -> Json.Encode.Value
+encodeWeddingRejection weddingrejection =
+    case weddingrejection of
+        NoOpenQuestion ->
+            Json.Encode.string "NoOpenQuestion"
+
+        VotingClosed ->
+            Json.Encode.string "VotingClosed"
+
+        UnknownQuestion ->
+            Json.Encode.string "UnknownQuestion"
+
+
+decodeWeddingRejection : Json.Decode.Decoder WeddingRejection
+decodeWeddingRejection =
+    Json.Decode.oneOf
+        [ decodeWeddingRejectionNoOpenQuestion
+        , decodeWeddingRejectionVotingClosed
+        , decodeWeddingRejectionUnknownQuestion
+        ]
+
+
+decodeWeddingRejectionNoOpenQuestion : Json.Decode.Decoder WeddingRejection
+decodeWeddingRejectionNoOpenQuestion =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "NoOpenQuestion" ->
+                    Json.Decode.succeed NoOpenQuestion
+
+                _ ->
+                    Json.Decode.fail "Expected variant NoOpenQuestion"
+        )
+        Json.Decode.string
+
+
+decodeWeddingRejectionVotingClosed : Json.Decode.Decoder WeddingRejection
+decodeWeddingRejectionVotingClosed =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "VotingClosed" ->
+                    Json.Decode.succeed VotingClosed
+
+                _ ->
+                    Json.Decode.fail "Expected variant VotingClosed"
+        )
+        Json.Decode.string
+
+
+decodeWeddingRejectionUnknownQuestion : Json.Decode.Decoder WeddingRejection
+decodeWeddingRejectionUnknownQuestion =
+    Json.Decode.andThen
+        (\str ->
+            case str of
+                "UnknownQuestion" ->
+                    Json.Decode.succeed UnknownQuestion
+
+                _ ->
+                    Json.Decode.fail "Expected variant UnknownQuestion"
+        )
+        Json.Decode.string
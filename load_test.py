@@ -22,13 +22,19 @@ def random_name():
 
 async def connect():
     async with websockets.connect(f"ws://localhost:8080/ws?uuid={uuid.uuid4()}") as websocket:
+        await websocket.send("{\"Navigate\":\"/wedding\"}")
         await asyncio.gather(producer(websocket), consumer(websocket))
 
 
+def event(content):
+    """Wrap a remote event into the message format the server expects."""
+    return f"{{\"Event\":{{\"event\":{content}}}}}"
+
+
 async def producer(websocket):
     """First send a name, then send guesses in an infinite loop with a random wait time"""
     name = random_name()
-    await websocket.send(f"{{\"SetName\":\"{name}\"}}")
+    await websocket.send(event(f"{{\"SetName\":\"{name}\"}}"))
     while True:
         await random_action(websocket)
         # Sleep a random amount of time between 1 and 3 seconds
@@ -38,14 +44,14 @@ async def producer(websocket):
 async def random_action(websocket):
     # Randomly choose an action from "Guess" (99%) or "Change Name" (1%)
     if random.random() < 0.01:
-        await websocket.send("\"RemoveName\"")
+        await websocket.send(event("\"RemoveName\""))
         # Sleep a random amount of time between 3 and 5 seconds
         await asyncio.sleep(3 + (2 * random.random()))
         # Set another random name
-        await websocket.send(f"{{\"SetName\":\"{random_name()}\"}}")
+        await websocket.send(event(f"{{\"SetName\":\"{random_name()}\"}}"))
     else:
         # Either guess "Bride" or "Groom" with 50% chance
-        await websocket.send(f"{{\"SetGuess\":\"{random.choice(['Bride', 'Groom'])}\"}}")
+        await websocket.send(event(f"{{\"SetGuess\":\"{random.choice(['Bride', 'Groom'])}\"}}"))
 
 
 async def consumer(websocket):
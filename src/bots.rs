@@ -0,0 +1,85 @@
+//! Computer players for Pomp. A bot sees the same `PompPlayerView` a human
+//! gets and answers with the `PompEvent` a human would send, so the game
+//! logic does not need to know who is playing.
+
+use std::cmp::Reverse;
+
+use serde::{Deserialize, Serialize};
+
+use crate::pomp::{Card, ElementColor, ElementVector, PompEvent, PompPlayerView};
+
+/// A strategy for a bot. It is asked for an action every few ticks and
+/// should be deterministic, so replaying the event log gives the same game.
+pub trait Bot {
+    /// `None` means the bot waits.
+    fn act(&self, view: &PompPlayerView) -> Option<PompEvent>;
+}
+
+/// The strategies a bot can play with. This is what is stored in the game
+/// state, strategies themselves have no state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BotKind {
+    Greedy,
+}
+
+impl BotKind {
+    pub fn strategy(self) -> &'static dyn Bot {
+        match self {
+            BotKind::Greedy => &Greedy,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BotKind::Greedy => "Greedy",
+        }
+    }
+}
+
+/// Buys the best card it can afford, otherwise it takes the element it is
+/// missing most for the card that is closest to being affordable.
+pub struct Greedy;
+
+impl Bot for Greedy {
+    fn act(&self, view: &PompPlayerView) -> Option<PompEvent> {
+        let me = view.my_inventory.as_ref()?;
+        let market = view.market.iter().flatten().flatten();
+
+        let affordable = |card: &&Card| card.cost.payment(&me.elements, &me.discount).is_some();
+        let reserved = best_card(view.my_reserved.iter().filter(affordable));
+        let on_market = best_card(market.clone().filter(affordable));
+        match (reserved, on_market) {
+            (Some(r), Some(m)) if m.points > r.points => return Some(PompEvent::BuyCard(m.id)),
+            (Some(r), _) => return Some(PompEvent::BuyReserved(r.id)),
+            (None, Some(m)) => return Some(PompEvent::BuyCard(m.id)),
+            (None, None) => {}
+        }
+
+        if me.energy == 0 {
+            return None;
+        }
+        let target = market.chain(&view.my_reserved).min_by_key(|card| {
+            (
+                missing(card, &me.elements, &me.discount).total(),
+                Reverse(card.points),
+            )
+        })?;
+        let missing = missing(target, &me.elements, &me.discount);
+        let color = ElementColor::BASIC
+            .iter()
+            .copied()
+            .max_by_key(|color| missing.get(*color))?;
+        Some(PompEvent::Buy(color))
+    }
+}
+
+/// Most points, then the cheapest one.
+fn best_card<'a>(cards: impl Iterator<Item = &'a Card>) -> Option<&'a Card> {
+    cards.max_by_key(|card| (card.points, Reverse(card.cost.total())))
+}
+
+/// Elements that are still missing for the card, ignoring wildcards.
+fn missing(card: &Card, elements: &ElementVector, discount: &ElementVector) -> ElementVector {
+    let after_discount = ElementVector::restricted_minus(&card.cost, discount);
+    ElementVector::restricted_minus(&after_discount, elements)
+}
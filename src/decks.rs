@@ -0,0 +1,287 @@
+//! Card decks of Pomp, read from a TOML file on startup so the game can be
+//! rebalanced without recompiling. See `decks.toml` for the format.
+
+use std::{fmt::Display, fs, io, path::Path, sync::OnceLock};
+
+use serde::{Deserialize, Serialize};
+
+use crate::pomp::{ElementColor, ElementVector, PowerEffect};
+
+/// File used when `POMP_DECKS` is not set.
+const DEFAULT_DECKS_FILE: &str = "decks.toml";
+
+/// Used when there is no decks file, so the server also runs from elsewhere.
+const BUILT_IN_DECKS: &str = include_str!("../decks.toml");
+
+static DECKS: OnceLock<DeckConfig> = OnceLock::new();
+
+#[derive(Debug, Deserialize)]
+pub struct DeckConfig {
+    /// Extra cost of random chaos cards, because their discount is a wildcard.
+    #[serde(default)]
+    pub chaos_surcharge: u32,
+    /// Power cards on the market get one element cheaper this often.
+    #[serde(default = "default_power_decay_seconds")]
+    pub power_decay_seconds: u32,
+    #[serde(rename = "tier")]
+    pub tiers: Vec<TierConfig>,
+}
+
+/// One row of the market and the deck it is refilled from.
+#[derive(Debug, Deserialize)]
+pub struct TierConfig {
+    pub market_slots: usize,
+    #[serde(default)]
+    pub cards_per_player: usize,
+    #[serde(default)]
+    pub kinds: Vec<CardKind>,
+    #[serde(default)]
+    pub fixed: Vec<FixedCard>,
+    #[serde(default)]
+    pub power: Vec<PowerCard>,
+}
+
+/// Random cards with these points and total cost. Kinds are drawn with a
+/// probability proportional to their weight.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CardKind {
+    pub weight: u32,
+    pub points: u32,
+    pub cost: u32,
+}
+
+/// A card that is in the deck exactly once, no matter how many players there are.
+#[derive(Debug, Deserialize)]
+pub struct FixedCard {
+    pub color: ElementColor,
+    pub points: u32,
+    pub cost: ElementVector,
+}
+
+/// Power cards get a random color and a random cost like the random cards.
+/// While they are on the market, their cost decays down to `min_cost`.
+#[derive(Debug, Deserialize)]
+pub struct PowerCard {
+    pub effect: PowerEffect,
+    #[serde(default)]
+    pub points: u32,
+    pub cost: u32,
+    pub min_cost: u32,
+    #[serde(default = "one")]
+    pub copies: usize,
+}
+
+/// The part of the decks that deals random cards. Every game keeps a copy,
+/// so refilling its decks later does not depend on the decks file anymore.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RandomCards {
+    pub chaos_surcharge: u32,
+    pub tiers: Vec<RandomTier>,
+}
+
+/// The random cards of one tier, see `TierConfig`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RandomTier {
+    pub cards_per_player: usize,
+    pub kinds: Vec<CardKind>,
+}
+
+fn default_power_decay_seconds() -> u32 {
+    5
+}
+
+fn one() -> usize {
+    1
+}
+
+#[derive(Debug)]
+pub enum DeckError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    NoTiers,
+    /// Tier index, counted from 1 like in the file.
+    NoMarketSlots(usize),
+    /// There are random cards per player, but no kinds to draw them from.
+    NoKinds(usize),
+    /// The tier would not have a single card.
+    EmptyTier(usize),
+    ZeroWeight(usize),
+    /// Cards must cost something, otherwise they can be bought for free.
+    FreeCard(usize),
+    /// Chaos can't be bought, so it can't be part of a cost.
+    ChaosCost(usize),
+    /// A power card would get more expensive over time.
+    MinCostTooHigh(usize),
+    /// Power cards need time to get cheaper.
+    NoDecay,
+}
+
+impl Display for DeckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeckError::Io(e) => write!(f, "could not read decks: {}", e),
+            DeckError::Parse(e) => write!(f, "decks are broken: {}", e),
+            DeckError::NoTiers => write!(f, "there must be at least one tier"),
+            DeckError::NoMarketSlots(tier) => write!(f, "tier {} has no market slots", tier),
+            DeckError::NoKinds(tier) => write!(f, "tier {} has random cards but no kinds", tier),
+            DeckError::EmptyTier(tier) => write!(f, "tier {} has no cards", tier),
+            DeckError::ZeroWeight(tier) => write!(f, "tier {} has a kind with weight 0", tier),
+            DeckError::FreeCard(tier) => write!(f, "tier {} has a card that costs nothing", tier),
+            DeckError::ChaosCost(tier) => write!(f, "tier {} has a card that costs chaos", tier),
+            DeckError::MinCostTooHigh(tier) => {
+                write!(f, "tier {} has a power card with min_cost above cost", tier)
+            }
+            DeckError::NoDecay => write!(f, "power_decay_seconds must not be 0"),
+        }
+    }
+}
+
+impl DeckConfig {
+    pub fn parse(toml: &str) -> Result<Self, DeckError> {
+        let config: DeckConfig = toml::from_str(toml).map_err(DeckError::Parse)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<(), DeckError> {
+        if self.tiers.is_empty() {
+            return Err(DeckError::NoTiers);
+        }
+        if self.power_decay_seconds == 0 {
+            return Err(DeckError::NoDecay);
+        }
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let number = i + 1;
+            if tier.market_slots == 0 {
+                return Err(DeckError::NoMarketSlots(number));
+            }
+            if tier.cards_per_player > 0 && tier.kinds.is_empty() {
+                return Err(DeckError::NoKinds(number));
+            }
+            let power_copies: usize = tier.power.iter().map(|power| power.copies).sum();
+            if tier.cards_per_player == 0 && tier.fixed.is_empty() && power_copies == 0 {
+                return Err(DeckError::EmptyTier(number));
+            }
+            if tier.kinds.iter().any(|kind| kind.weight == 0) {
+                return Err(DeckError::ZeroWeight(number));
+            }
+            let free_kind = tier.kinds.iter().any(|kind| kind.cost == 0);
+            let free_fixed = tier.fixed.iter().any(|card| card.cost.total() == 0);
+            let free_power = tier.power.iter().any(|card| card.min_cost == 0);
+            if free_kind || free_fixed || free_power {
+                return Err(DeckError::FreeCard(number));
+            }
+            if tier
+                .fixed
+                .iter()
+                .any(|card| card.cost.get(ElementColor::Chaos) > 0)
+            {
+                return Err(DeckError::ChaosCost(number));
+            }
+            if tier.power.iter().any(|card| card.min_cost > card.cost) {
+                return Err(DeckError::MinCostTooHigh(number));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn random_cards(&self) -> RandomCards {
+        RandomCards {
+            chaos_surcharge: self.chaos_surcharge,
+            tiers: self
+                .tiers
+                .iter()
+                .map(|tier| RandomTier {
+                    cards_per_player: tier.cards_per_player,
+                    kinds: tier.kinds.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Reads the decks from `POMP_DECKS` or "./decks.toml". Without a file the
+    /// built-in decks are used, but a broken file is always an error.
+    pub fn from_env() -> Result<Self, DeckError> {
+        let path = std::env::var_os("POMP_DECKS");
+        let path = path
+            .as_deref()
+            .map(Path::new)
+            .unwrap_or_else(|| Path::new(DEFAULT_DECKS_FILE));
+        match fs::read_to_string(path) {
+            Ok(toml) => DeckConfig::parse(&toml),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => DeckConfig::parse(BUILT_IN_DECKS),
+            Err(e) => Err(DeckError::Io(e)),
+        }
+    }
+}
+
+/// Makes the decks available to all games. Call this once on startup.
+pub fn install(config: DeckConfig) {
+    if DECKS.set(config).is_err() {
+        panic!("Decks can only be installed once.");
+    }
+}
+
+/// The installed decks, or the built-in ones if nothing was installed.
+pub fn current() -> &'static DeckConfig {
+    DECKS.get_or_init(|| DeckConfig::parse(BUILT_IN_DECKS).expect("Built-in decks are broken."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_built_in_decks() {
+        let config = DeckConfig::parse(BUILT_IN_DECKS).unwrap();
+        assert_eq!(4, config.tiers.len());
+    }
+
+    #[test]
+    fn test_validation() {
+        let fixed = r#"
+            [[tier]]
+            market_slots = 2
+            [[tier.fixed]]
+            color = "Fire"
+            points = 1
+            cost = { water = 2 }
+        "#;
+        let config = DeckConfig::parse(fixed).unwrap();
+        assert_eq!(2, config.tiers[0].fixed[0].cost.total());
+
+        let empty = "[[tier]]\nmarket_slots = 5";
+        assert!(matches!(
+            DeckConfig::parse(empty),
+            Err(DeckError::EmptyTier(1))
+        ));
+        let zero_weight = r#"
+            [[tier]]
+            market_slots = 5
+            cards_per_player = 1
+            kinds = [{ weight = 0, points = 1, cost = 3 }]
+        "#;
+        assert!(matches!(
+            DeckConfig::parse(zero_weight),
+            Err(DeckError::ZeroWeight(1))
+        ));
+        let chaos_cost = fixed.replace("water", "chaos");
+        assert!(matches!(
+            DeckConfig::parse(&chaos_cost),
+            Err(DeckError::ChaosCost(1))
+        ));
+        let power = r#"
+            [[tier]]
+            market_slots = 1
+            [[tier.power]]
+            effect = { ElementIncome = "Fire" }
+            cost = 5
+            min_cost = 6
+        "#;
+        assert!(matches!(
+            DeckConfig::parse(power),
+            Err(DeckError::MinCostTooHigh(1))
+        ));
+        assert!(matches!(DeckConfig::parse(""), Err(DeckError::Parse(_))));
+    }
+}
@@ -0,0 +1,316 @@
+//! Append only log of everything that happened to a live state. Each route
+//! instance gets one newline delimited JSON file, which `pomp replay` can feed
+//! back into a fresh live state to find out how a game ended up where it is.
+
+use std::{
+    fmt::Display,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+
+use crate::game::{RemoteEvent, SharedLiveState, UserUuid};
+
+/// Directory used when `POMP_LOG_DIR` is not set.
+const DEFAULT_LOG_DIR: &str = "logs";
+
+/// One line of the log.
+#[derive(Serialize, Deserialize)]
+pub struct LogRecord {
+    pub at: u64, // Milliseconds since the unix epoch.
+    #[serde(flatten)]
+    pub entry: LogEntry,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum LogEntry {
+    /// The full live state when the actor started. After a restart there is
+    /// only a new one if the restored snapshot is behind the log.
+    Init {
+        route: String,
+        state: serde_json::Value,
+    },
+    /// Only accepted events are logged, rejected ones did not change anything.
+    Event {
+        sender: UserUuid,
+        event: serde_json::Value,
+    },
+    /// Consecutive ticks are written as one entry.
+    Ticks {
+        count: u32,
+    },
+    Join {
+        user: UserUuid,
+    },
+    Leave {
+        user: UserUuid,
+    },
+    Timer {
+        payload: serde_json::Value,
+    },
+}
+
+pub struct EventLog {
+    file: File,
+    entries: u64,       // Lines in the file, including ones from before a restart.
+    pending_ticks: u32, // Ticks that are not written yet.
+}
+
+impl EventLog {
+    /// Appends to the log of the route in `POMP_LOG_DIR` or "./logs".
+    pub fn open(route: &str) -> io::Result<Self> {
+        let dir = match std::env::var_os("POMP_LOG_DIR") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(DEFAULT_LOG_DIR),
+        };
+        EventLog::open_in(&dir, route)
+    }
+
+    fn open_in(dir: &Path, route: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let name = route.trim_start_matches('/').replace('/', "_");
+        let path = dir.join(format!("{}.ndjson", name));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut entries = 0;
+        for line in BufReader::new(File::open(&path)?).lines() {
+            if !line?.trim().is_empty() {
+                entries += 1;
+            }
+        }
+        Ok(EventLog {
+            file,
+            entries,
+            pending_ticks: 0,
+        })
+    }
+
+    /// Number of entries written so far. Ticks that are not written yet don't
+    /// count, `flush_ticks` first.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Ticks are only counted here and written as one `Ticks` entry before
+    /// the next other entry or on `flush_ticks`.
+    pub fn tick(&mut self) {
+        self.pending_ticks += 1;
+    }
+
+    pub fn flush_ticks(&mut self) {
+        if self.pending_ticks > 0 {
+            let count = std::mem::take(&mut self.pending_ticks);
+            self.write(LogEntry::Ticks { count });
+        }
+    }
+
+    /// Lines are written right away, so nothing is lost when the server
+    /// crashes. Only ticks since the last entry are lost then.
+    pub fn append(&mut self, entry: LogEntry) {
+        self.flush_ticks();
+        self.write(entry);
+    }
+
+    fn write(&mut self, entry: LogEntry) {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let mut line = serde_json::to_vec(&LogRecord { at, entry })
+            .expect("Serde error while encoding to json.");
+        line.push(b'\n');
+        match self.file.write_all(&line) {
+            Ok(()) => self.entries += 1,
+            Err(e) => error!("Could not write to event log: {}", e),
+        }
+    }
+}
+
+/// Reads a log file written by `EventLog`.
+pub fn read_log(path: &Path) -> Result<Vec<LogRecord>, ReplayError> {
+    let file = File::open(path).map_err(ReplayError::Io)?;
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(ReplayError::Io)?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| ReplayError::BadRecord(i + 1, e))?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The route of the first `Init` entry, this decides the live state type.
+pub fn logged_route(records: &[LogRecord]) -> Option<&str> {
+    records.iter().find_map(|record| match &record.entry {
+        LogEntry::Init { route, .. } => Some(route.as_str()),
+        _ => None,
+    })
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// Line number and why it could not be parsed.
+    BadRecord(usize, serde_json::Error),
+    /// The log does not start with an `Init` entry.
+    MissingInit,
+    /// No registered route matches the logged route.
+    UnknownRoute(String),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "could not read log: {}", e),
+            ReplayError::BadRecord(line, e) => write!(f, "line {} is broken: {}", line, e),
+            ReplayError::MissingInit => write!(f, "log does not start with an Init entry"),
+            ReplayError::UnknownRoute(route) => write!(f, "no live state for route {}", route),
+        }
+    }
+}
+
+/// Result of a replay. Divergences are the indices of entries the replay does
+/// not agree with: `Init` entries that differ from the replayed state, e.g.
+/// because the server crashed before it could persist the latest state, or
+/// events that are rejected now.
+pub struct Replay<S> {
+    pub state: S,
+    pub divergences: Vec<usize>,
+}
+
+/// Feeds the log into the live state from its first `Init` entry. Effects are
+/// ignored, timers that fired are part of the log anyway.
+pub fn replay<S>(records: &[LogRecord]) -> Result<Replay<S>, ReplayError>
+where
+    S: SharedLiveState,
+{
+    let mut state: Option<S> = None;
+    let mut divergences = Vec::new();
+    for (i, record) in records.iter().enumerate() {
+        let bad_record = |e| ReplayError::BadRecord(i + 1, e);
+        if let LogEntry::Init { state: logged, .. } = &record.entry {
+            if let Some(state) = &state {
+                if &serde_json::to_value(state).map_err(bad_record)? != logged {
+                    divergences.push(i);
+                }
+            }
+            state = Some(serde_json::from_value(logged.clone()).map_err(bad_record)?);
+            continue;
+        }
+        let state = state.as_mut().ok_or(ReplayError::MissingInit)?;
+        let _effect = match &record.entry {
+            LogEntry::Init { .. } => unreachable!("Handled above"),
+            LogEntry::Event { sender, event } => {
+                let event = S::Event::deserialize(&event.to_string()).map_err(bad_record)?;
+                match state.process_remote_event(event, sender.clone()) {
+                    Ok(effect) => effect,
+                    Err(_) => {
+                        // It was accepted when it was logged.
+                        divergences.push(i);
+                        continue;
+                    }
+                }
+            }
+            LogEntry::Ticks { count } => {
+                for _ in 0..*count {
+                    let _effect = state.process_tick();
+                }
+                continue;
+            }
+            LogEntry::Join { user } => state.join_user(user.clone()),
+            LogEntry::Leave { user } => state.leave_user(user.clone()),
+            LogEntry::Timer { payload } => {
+                let payload = serde_json::from_value(payload.clone()).map_err(bad_record)?;
+                state.process_timer(payload)
+            }
+        };
+    }
+    Ok(Replay {
+        state: state.ok_or(ReplayError::MissingInit)?,
+        divergences,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup;
+
+    fn record(entry: LogEntry) -> LogRecord {
+        LogRecord { at: 0, entry }
+    }
+
+    #[test]
+    fn test_replay_setup() {
+        let player =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        let initial = serde_json::to_value(setup::GameState::new(1)).unwrap();
+        let records = [
+            record(LogEntry::Init {
+                route: "/pomp/1/setup".to_owned(),
+                state: initial,
+            }),
+            record(LogEntry::Join {
+                user: player.clone(),
+            }),
+            record(LogEntry::Event {
+                sender: player.clone(),
+                event: serde_json::json!({"SetName": "Alice"}),
+            }),
+        ];
+        // Going through json checks that the log can be written and read.
+        let json: Vec<String> = records
+            .iter()
+            .map(|r| serde_json::to_string(r).unwrap())
+            .collect();
+        let records: Vec<LogRecord> = json
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        let replay = replay::<setup::GameState>(&records).unwrap();
+        assert!(replay.divergences.is_empty());
+        assert_eq!(1, replay.state.data.len());
+        assert_eq!("Alice", replay.state.data[0].1.name);
+    }
+
+    #[test]
+    fn test_ticks_share_a_line() {
+        let dir = std::env::temp_dir().join(format!("pomp-log-{}", std::process::id()));
+        let mut log = EventLog::open_in(&dir, "/pomp/1/setup").unwrap();
+        let initial = serde_json::to_value(setup::GameState::new(1)).unwrap();
+        log.append(LogEntry::Init {
+            route: "/pomp/1/setup".to_owned(),
+            state: initial,
+        });
+        log.tick();
+        log.tick();
+        log.tick();
+        log.flush_ticks();
+        log.flush_ticks();
+        assert_eq!(2, log.entries());
+
+        // Reopening after a restart keeps counting.
+        let log = EventLog::open_in(&dir, "/pomp/1/setup").unwrap();
+        assert_eq!(2, log.entries());
+        let records = read_log(&dir.join("pomp_1_setup.ndjson")).unwrap();
+        assert!(matches!(records[1].entry, LogEntry::Ticks { count: 3 }));
+        assert!(replay::<setup::GameState>(&records).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_replay_needs_init() {
+        let records = vec![record(LogEntry::Ticks { count: 1 })];
+        assert!(matches!(
+            replay::<setup::GameState>(&records),
+            Err(ReplayError::MissingInit)
+        ));
+    }
+}
@@ -0,0 +1,252 @@
+module JsonPatch exposing (Patch, apply, decodePatch)
+
+{-| Minimal JSON Patch (RFC 6902) implementation. The server sends the changes
+to the live state as a patch, so we need to apply it to the last full state we
+know about.
+
+Elm can't modify a `Value` directly, so we go through our own `Json` type.
+Only the operations the server actually produces are supported: add, remove
+and replace. The server sends a snapshot instead of replacing the whole
+document, but a replace of the root ("") works anyway.
+
+-}
+
+import Dict exposing (Dict)
+import Json.Decode as Decode exposing (Decoder)
+import Json.Encode as Encode exposing (Value)
+
+
+type alias Patch =
+    List Operation
+
+
+type Operation
+    = Add (List String) Json
+    | Remove (List String)
+    | Replace (List String) Json
+
+
+type Json
+    = JNull
+    | JBool Bool
+    | JNumber Float
+    | JString String
+    | JArray (List Json)
+    | JObject (Dict String Json)
+
+
+{-| Applies all operations in order. Fails if any path does not exist.
+-}
+apply : Patch -> Value -> Result String Value
+apply patch value =
+    case Decode.decodeValue decodeJson value of
+        Ok json ->
+            List.foldl (\op -> Result.andThen (applyOperation op)) (Ok json) patch
+                |> Result.map encodeJson
+
+        Err e ->
+            Err (Decode.errorToString e)
+
+
+
+-- Decoding --------------------------------------------------------------------
+--------------------------------------------------------------------------------
+
+
+decodePatch : Decoder Patch
+decodePatch =
+    Decode.list decodeOperation
+
+
+decodeOperation : Decoder Operation
+decodeOperation =
+    Decode.field "op" Decode.string
+        |> Decode.andThen
+            (\op ->
+                case op of
+                    "add" ->
+                        Decode.map2 Add decodePath (Decode.field "value" decodeJson)
+
+                    "remove" ->
+                        Decode.map Remove decodePath
+
+                    "replace" ->
+                        Decode.map2 Replace decodePath (Decode.field "value" decodeJson)
+
+                    _ ->
+                        Decode.fail ("Unsupported patch operation: " ++ op)
+            )
+
+
+{-| Splits a JSON Pointer like "/others/0/name" into its unescaped segments.
+-}
+decodePath : Decoder (List String)
+decodePath =
+    Decode.field "path" Decode.string
+        |> Decode.map
+            (\path ->
+                String.split "/" path
+                    |> List.drop 1
+                    |> List.map (String.replace "~1" "/" >> String.replace "~0" "~")
+            )
+
+
+decodeJson : Decoder Json
+decodeJson =
+    Decode.oneOf
+        [ Decode.null JNull
+        , Decode.map JBool Decode.bool
+        , Decode.map JNumber Decode.float
+        , Decode.map JString Decode.string
+        , Decode.map JArray (Decode.list (Decode.lazy (\_ -> decodeJson)))
+        , Decode.map JObject (Decode.dict (Decode.lazy (\_ -> decodeJson)))
+        ]
+
+
+encodeJson : Json -> Value
+encodeJson json =
+    case json of
+        JNull ->
+            Encode.null
+
+        JBool b ->
+            Encode.bool b
+
+        JNumber n ->
+            Encode.float n
+
+        JString s ->
+            Encode.string s
+
+        JArray items ->
+            Encode.list encodeJson items
+
+        JObject fields ->
+            Encode.dict identity encodeJson fields
+
+
+
+-- Applying --------------------------------------------------------------------
+--------------------------------------------------------------------------------
+
+
+applyOperation : Operation -> Json -> Result String Json
+applyOperation op json =
+    case op of
+        Replace [] value ->
+            Ok value
+
+        Add path value ->
+            update path (\parent key -> insert key value parent) json
+
+        Remove path ->
+            update path (\parent key -> remove key parent) json
+
+        Replace path value ->
+            update path (\parent key -> remove key parent |> Result.andThen (insert key value)) json
+
+
+{-| Walks down the path and calls `f` with the parent container and the last
+segment of the path.
+-}
+update : List String -> (Json -> String -> Result String Json) -> Json -> Result String Json
+update path f json =
+    case path of
+        [] ->
+            Err "Patching the document root is not supported"
+
+        [ key ] ->
+            f json key
+
+        key :: rest ->
+            get key json
+                |> Result.andThen (update rest f)
+                |> Result.andThen (\child -> set key child json)
+
+
+get : String -> Json -> Result String Json
+get key json =
+    case json of
+        JObject fields ->
+            Dict.get key fields |> Result.fromMaybe ("Missing field " ++ key)
+
+        JArray items ->
+            index key items
+                |> Result.andThen (\i -> List.drop i items |> List.head |> Result.fromMaybe ("Missing index " ++ key))
+
+        _ ->
+            Err ("Can't look up " ++ key ++ " in a primitive value")
+
+
+set : String -> Json -> Json -> Result String Json
+set key child json =
+    case json of
+        JObject fields ->
+            Ok (JObject (Dict.insert key child fields))
+
+        JArray items ->
+            index key items
+                |> Result.map (\i -> JArray (List.take i items ++ child :: List.drop (i + 1) items))
+
+        _ ->
+            Err ("Can't set " ++ key ++ " in a primitive value")
+
+
+insert : String -> Json -> Json -> Result String Json
+insert key value json =
+    case json of
+        JObject fields ->
+            Ok (JObject (Dict.insert key value fields))
+
+        JArray items ->
+            if key == "-" then
+                Ok (JArray (items ++ [ value ]))
+
+            else
+                String.toInt key
+                    |> Maybe.andThen
+                        (\i ->
+                            if i >= 0 && i <= List.length items then
+                                Just (JArray (List.take i items ++ value :: List.drop i items))
+
+                            else
+                                Nothing
+                        )
+                    |> Result.fromMaybe ("Invalid index " ++ key)
+
+        _ ->
+            Err ("Can't add " ++ key ++ " to a primitive value")
+
+
+remove : String -> Json -> Result String Json
+remove key json =
+    case json of
+        JObject fields ->
+            if Dict.member key fields then
+                Ok (JObject (Dict.remove key fields))
+
+            else
+                Err ("Missing field " ++ key)
+
+        JArray items ->
+            index key items
+                |> Result.map (\i -> JArray (List.take i items ++ List.drop (i + 1) items))
+
+        _ ->
+            Err ("Can't remove " ++ key ++ " from a primitive value")
+
+
+{-| Parses an array index that must point at an existing element.
+-}
+index : String -> List a -> Result String Int
+index key items =
+    case String.toInt key of
+        Just i ->
+            if i >= 0 && i < List.length items then
+                Ok i
+
+            else
+                Err ("Index out of bounds " ++ key)
+
+        Nothing ->
+            Err ("Invalid index " ++ key)
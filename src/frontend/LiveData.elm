@@ -6,10 +6,86 @@ when that is done, it also needs to move to /generated instead of /src/frontend.
 
 import Json.Decode
 import Json.Encode exposing (Value)
+import JsonPatch
 import PompData exposing (PompEvent, encodePompEvent)
 import WeddingData
 
 
+{-| The live state as it was sent by the server, before decoding it into the
+page specific type. Patches are applied to this.
+-}
+type alias RawLiveState =
+    { route : String
+    , version : Int
+    , data : Value
+    }
+
+
+{-| Elm version of
+
+    enum LiveStateFrame {
+        Snapshot { route, version, data },
+        Patch { route, base, version, patch },
+    }
+
+-}
+type LiveStateFrame
+    = Snapshot RawLiveState
+    | Patch { route : String, base : Int, version : Int, patch : JsonPatch.Patch }
+
+
+decodeLiveStateFrame : Json.Decode.Decoder LiveStateFrame
+decodeLiveStateFrame =
+    Json.Decode.oneOf
+        [ Json.Decode.map3 RawLiveState
+            (Json.Decode.at [ "route" ] Json.Decode.string)
+            (Json.Decode.at [ "version" ] Json.Decode.int)
+            (Json.Decode.at [ "data" ] Json.Decode.value)
+            |> Json.Decode.map Snapshot
+        , Json.Decode.map4 (\route base version patch -> Patch { route = route, base = base, version = version, patch = patch })
+            (Json.Decode.at [ "route" ] Json.Decode.string)
+            (Json.Decode.at [ "base" ] Json.Decode.int)
+            (Json.Decode.at [ "version" ] Json.Decode.int)
+            (Json.Decode.at [ "patch" ] JsonPatch.decodePatch)
+        ]
+
+
+{-| Computes the new raw live state. This fails if the patch was made for a
+different version than the one we have, then we need to ask for a `Resync`.
+-}
+applyLiveStateFrame : Maybe RawLiveState -> LiveStateFrame -> Result String RawLiveState
+applyLiveStateFrame current frame =
+    case ( frame, current ) of
+        ( Snapshot raw, _ ) ->
+            Ok raw
+
+        ( Patch p, Just raw ) ->
+            if p.route == raw.route && p.base == raw.version then
+                JsonPatch.apply p.patch raw.data
+                    |> Result.map (\data -> { route = p.route, version = p.version, data = data })
+
+            else
+                Err "Patch does not fit the current live state"
+
+        ( Patch _, Nothing ) ->
+            Err "Got a patch before the first snapshot"
+
+
+{-| Turns the raw live state back into the `{ route, data }` shape that
+`decodeLiveStateOneRouteOnly` expects.
+-}
+encodeRawLiveState : RawLiveState -> Value
+encodeRawLiveState raw =
+    Json.Encode.object [ ( "route", Json.Encode.string raw.route ), ( "data", raw.data ) ]
+
+
+{-| Asks the server for a full snapshot after a patch could not be applied.
+-}
+encodeResync : Value
+encodeResync =
+    Json.Encode.string "Resync"
+
+
 decodeLiveStateOneRouteOnly : String -> Json.Decode.Decoder page -> (page -> wrapped) -> Json.Decode.Decoder wrapped
 decodeLiveStateOneRouteOnly routeId decoder wrapper =
     Json.Decode.at [ "route" ] Json.Decode.string
@@ -24,6 +100,203 @@ decodeLiveStateOneRouteOnly routeId decoder wrapper =
             )
 
 
+{-| Elm version of the frame the server sends when navigating to a route failed.
+
+    struct ReportRouteError {
+        route: String,
+        error: RouteError,
+    }
+
+-}
+type alias RouteError =
+    { route : String
+    , error : String
+    }
+
+
+decodeRouteError : Json.Decode.Decoder RouteError
+decodeRouteError =
+    Json.Decode.field "route_error"
+        (Json.Decode.map2 RouteError
+            (Json.Decode.at [ "route" ] Json.Decode.string)
+            (Json.Decode.at [ "error" ] Json.Decode.string)
+        )
+
+
+routeErrorText : RouteError -> String
+routeErrorText e =
+    case e.error of
+        "NotFound" ->
+            "There is nothing at " ++ e.route ++ "."
+
+        "SetupTypeMismatch" ->
+            "The game at " ++ e.route ++ " could not be set up."
+
+        "AlreadyInitialised" ->
+            "The game at " ++ e.route ++ " is already running."
+
+        _ ->
+            "The server could not open " ++ e.route ++ ". Please reload."
+
+
+{-| Elm version of a toast the server sends us. These are not part of the live
+state, so we have to keep them around ourselves until they are dismissed.
+Sounds are already played by main.ts.
+
+    struct Notification {
+        level: NotificationLevel,
+        text: String,
+        sound: Option<String>,
+    }
+
+-}
+type alias Notification =
+    { level : String
+    , text : String
+    }
+
+
+decodeNotification : Json.Decode.Decoder Notification
+decodeNotification =
+    Json.Decode.field "notification"
+        (Json.Decode.map2 Notification
+            (Json.Decode.at [ "level" ] Json.Decode.string)
+            (Json.Decode.at [ "text" ] Json.Decode.string)
+        )
+
+
+notificationColor : Notification -> String
+notificationColor notification =
+    case notification.level of
+        "Success" ->
+            "bg-green-300"
+
+        "Warning" ->
+            "bg-yellow-300"
+
+        _ ->
+            "bg-blue-200"
+
+
+{-| Elm version of the answer to a remote event we sent.
+
+    struct EventReply<R> {
+        id: Option<u64>,
+        result: EventResult<R>,
+    }
+
+The rejection reason depends on the route, so we keep it as a string here.
+
+-}
+type alias EventReply =
+    { id : Maybe Int
+    , result : EventResult
+    }
+
+
+type EventResult
+    = Ack
+    | Rejected String
+    | Malformed String
+
+
+decodeEventReply : Json.Decode.Decoder EventReply
+decodeEventReply =
+    Json.Decode.field "reply"
+        (Json.Decode.map2 EventReply
+            (Json.Decode.field "id" (Json.Decode.nullable Json.Decode.int))
+            (Json.Decode.field "result" decodeEventResult)
+        )
+
+
+decodeEventResult : Json.Decode.Decoder EventResult
+decodeEventResult =
+    Json.Decode.oneOf
+        [ Json.Decode.string
+            |> Json.Decode.andThen
+                (\s ->
+                    if s == "Ack" then
+                        Json.Decode.succeed Ack
+
+                    else
+                        Json.Decode.fail ("Unknown event result: " ++ s)
+                )
+        , Json.Decode.field "Rejected" Json.Decode.string |> Json.Decode.map Rejected
+        , Json.Decode.field "Malformed" Json.Decode.string |> Json.Decode.map Malformed
+        ]
+
+
+{-| Turns a rejection into a warning we can show as a toast.
+Returns Nothing if there is nothing to tell the user.
+-}
+eventResultNotification : EventResult -> Maybe Notification
+eventResultNotification result =
+    case result of
+        Ack ->
+            Nothing
+
+        Rejected reason ->
+            Just { level = "Warning", text = rejectionText reason }
+
+        Malformed _ ->
+            Just { level = "Warning", text = "The server did not understand this action. Please reload." }
+
+
+rejectionText : String -> String
+rejectionText reason =
+    case reason of
+        "GameOver" ->
+            "The game is already over."
+
+        "NotAPlayer" ->
+            "You are not playing in this game."
+
+        "NotEnoughEnergy" ->
+            "You don't have enough energy."
+
+        "ChaosNotForSale" ->
+            "Chaos can't be bought with energy."
+
+        "CardNotOnMarket" ->
+            "Someone else bought this card first."
+
+        "CardNotReserved" ->
+            "You did not reserve this card."
+
+        "ReserveFull" ->
+            "You can't reserve more than 3 cards."
+
+        "NotEnoughElements" ->
+            "You don't have enough elements for this card."
+
+        "NotInLobby" ->
+            "You are not in this lobby."
+
+        "NotHost" ->
+            "Only the host can change the seed, the rules and the bots."
+
+        "InvalidRules" ->
+            "These rules would break the game."
+
+        "NotABot" ->
+            "Only bots can be removed."
+
+        "AlreadyStarted" ->
+            "The game has already started."
+
+        "NoOpenQuestion" ->
+            "Es gibt gerade keine Frage."
+
+        "VotingClosed" ->
+            "Die Abstimmung ist schon geschlossen."
+
+        "UnknownQuestion" ->
+            "Diese Frage gibt es nicht."
+
+        _ ->
+            "The server rejected this action."
+
+
 {-| Overall remote event. Required for now until I figure out better typing.
 -}
 type RemoteEvent
@@ -32,8 +305,24 @@ type RemoteEvent
     | WeddingRemoteEventWrapper WeddingData.WeddingEvent
 
 
-encodeRemoteEvent : RemoteEvent -> Value
-encodeRemoteEvent e =
+{-| Wraps the remote event into the client message the websocket expects.
+The id comes back in the `EventReply`.
+Navigation messages are sent directly from main.ts.
+-}
+encodeRemoteEvent : Int -> RemoteEvent -> Value
+encodeRemoteEvent id e =
+    Json.Encode.object
+        [ ( "Event"
+          , Json.Encode.object
+                [ ( "id", Json.Encode.int id )
+                , ( "event", encodeRemoteEventContent e )
+                ]
+          )
+        ]
+
+
+encodeRemoteEventContent : RemoteEvent -> Value
+encodeRemoteEventContent e =
     case e of
         -- TODO: Attach the curret route we are on so we can drop irrelevant messages on the server.
         PompRemoteEventWrapper x ->
@@ -51,38 +340,98 @@ encodeRemoteEvent e =
     struct PlayerSetupData {
         is_ready: bool,
         name: String,
+        bot: Option<BotKind>,
     }
 
     struct LiveState {
         data: Vec<PlayerSetupData>,
         my_index: isize,
+        seed: Option<u32>,
+        rules: PompRules,
     }
 
 -}
 type alias SetupLiveState =
     { data : List PlayerSetupData
     , myIndex : Int
+    , seed : Maybe Int
+    , rules : PompRules
     }
 
 
 decodeSetupLiveState : Json.Decode.Decoder SetupLiveState
 decodeSetupLiveState =
-    Json.Decode.map2 SetupLiveState
+    Json.Decode.map4 SetupLiveState
         (Json.Decode.at [ "data" ] (Json.Decode.list decodePlayerSetupData))
         (Json.Decode.at [ "my_index" ] Json.Decode.int)
+        (Json.Decode.at [ "seed" ] (Json.Decode.nullable Json.Decode.int))
+        (Json.Decode.at [ "rules" ] decodePompRules)
+
 
+{-| Elm version of
+
+    struct PompRules {
+        score_to_win: u32,
+        grace_seconds: u32,
+        ticks_per_second: u32,
+        ticks_per_energy: u32,
+        market_slots: Option<usize>,
+        deck_percent: u32,
+        refill_decks: bool,
+    }
 
+-}
+type alias PompRules =
+    { scoreToWin : Int
+    , graceSeconds : Int
+    , ticksPerSecond : Int
+    , ticksPerEnergy : Int
+    , marketSlots : Maybe Int
+    , deckPercent : Int
+    , refillDecks : Bool
+    }
+
+
+decodePompRules : Json.Decode.Decoder PompRules
+decodePompRules =
+    Json.Decode.map7 PompRules
+        (Json.Decode.at [ "score_to_win" ] Json.Decode.int)
+        (Json.Decode.at [ "grace_seconds" ] Json.Decode.int)
+        (Json.Decode.at [ "ticks_per_second" ] Json.Decode.int)
+        (Json.Decode.at [ "ticks_per_energy" ] Json.Decode.int)
+        (Json.Decode.at [ "market_slots" ] (Json.Decode.nullable Json.Decode.int))
+        (Json.Decode.at [ "deck_percent" ] Json.Decode.int)
+        (Json.Decode.at [ "refill_decks" ] Json.Decode.bool)
+
+
+encodePompRules : PompRules -> Value
+encodePompRules rules =
+    Json.Encode.object
+        [ ( "score_to_win", Json.Encode.int rules.scoreToWin )
+        , ( "grace_seconds", Json.Encode.int rules.graceSeconds )
+        , ( "ticks_per_second", Json.Encode.int rules.ticksPerSecond )
+        , ( "ticks_per_energy", Json.Encode.int rules.ticksPerEnergy )
+        , ( "market_slots", Maybe.map Json.Encode.int rules.marketSlots |> Maybe.withDefault Json.Encode.null )
+        , ( "deck_percent", Json.Encode.int rules.deckPercent )
+        , ( "refill_decks", Json.Encode.bool rules.refillDecks )
+        ]
+
+
+{-| The bot is the name of its strategy, like "Greedy".
+-}
 type alias PlayerSetupData =
     { isReady : Bool
     , name : String
+    , bot : Maybe String
     }
 
 
 decodePlayerSetupData : Json.Decode.Decoder PlayerSetupData
 decodePlayerSetupData =
-    Json.Decode.map2 PlayerSetupData
+    Json.Decode.map3 PlayerSetupData
         (Json.Decode.at [ "is_ready" ] Json.Decode.bool)
         (Json.Decode.at [ "name" ] Json.Decode.string)
+        (Json.Decode.at [ "bot" ] (Json.Decode.nullable Json.Decode.string))
 
 
 {-| Elm version of
@@ -90,6 +439,10 @@ decodePlayerSetupData =
     enum RemoteEvent {
         SetName(String),
         SetReady(bool),
+        SetSeed(Option<u32>),
+        SetRules(PompRules),
+        AddBot(BotKind),
+        RemoveBot(usize),
         StartGame,
     }
 
@@ -97,6 +450,10 @@ decodePlayerSetupData =
 type SetupRemoteEvent
     = SetName String
     | SetReady Bool
+    | SetSeed (Maybe Int)
+    | SetRules PompRules
+    | AddBot String
+    | RemoveBot Int
     | StartGame
 
 
@@ -109,5 +466,17 @@ encodeSetupRemoteEvent e =
         SetReady ready ->
             Json.Encode.object [ ( "SetReady", Json.Encode.bool ready ) ]
 
+        SetSeed seed ->
+            Json.Encode.object [ ( "SetSeed", Maybe.map Json.Encode.int seed |> Maybe.withDefault Json.Encode.null ) ]
+
+        SetRules rules ->
+            Json.Encode.object [ ( "SetRules", encodePompRules rules ) ]
+
+        AddBot kind ->
+            Json.Encode.object [ ( "AddBot", Json.Encode.string kind ) ]
+
+        RemoveBot index ->
+            Json.Encode.object [ ( "RemoveBot", Json.Encode.int index ) ]
+
         StartGame ->
             Json.Encode.string "StartGame"
@@ -3,6 +3,8 @@ module Main exposing (main)
 import Browser
 import FontAwesome.Styles
 import Html exposing (Html)
+import Html.Attributes exposing (class)
+import Html.Events exposing (onClick)
 import Json.Decode
 import LiveData
 import Page.Loading
@@ -10,6 +12,8 @@ import Page.Pomp
 import Page.Setup
 import Page.Wedding
 import PompData
+import Process
+import Task
 import Websocket
 import WeddingData
 
@@ -23,35 +27,112 @@ type alias Flags =
 
 type alias Model =
     { liveState : Maybe LiveState
+    , rawLiveState : Maybe LiveData.RawLiveState
+    , routeError : Maybe LiveData.RouteError
+    , notifications : List ( Int, LiveData.Notification )
+    , nextNotificationId : Int
+    , nextEventId : Int
     }
 
 
+{-| How long a notification is shown before it disappears on its own.
+-}
+notificationTimeout : Float
+notificationTimeout =
+    5000
+
+
 type Msg
     = RemoteEventBox LiveData.RemoteEvent
       --| ClientMsg msg
-    | NewServerState LiveState
+    | LiveStateFrameReceived LiveData.LiveStateFrame
     | BrokenLiveState String
+    | RouteErrorReceived LiveData.RouteError
+    | DismissRouteError
+    | NotificationReceived LiveData.Notification
+    | DismissNotification Int
+    | EventReplyReceived LiveData.EventReply
 
 
 main : Program Flags Model Msg
 main =
     Browser.element
-        { init = \_ -> ( { liveState = Nothing }, Cmd.none )
+        { init = \_ -> ( init, Cmd.none )
         , update = update
-        , view = \model -> Html.div [] [ FontAwesome.Styles.css, view model ]
+        , view =
+            \model ->
+                Html.div []
+                    [ FontAwesome.Styles.css
+                    , viewRouteError model.routeError
+                    , view model
+                    , viewNotifications model.notifications
+                    ]
         , subscriptions = \_ -> Websocket.subscribe sandboxLiveStateParser
         }
 
 
+init : Model
+init =
+    { liveState = Nothing
+    , rawLiveState = Nothing
+    , routeError = Nothing
+    , notifications = []
+    , nextNotificationId = 0
+    , nextEventId = 0
+    }
+
+
 update : Msg -> Model -> ( Model, Cmd Msg )
 update msg model =
     case msg of
         RemoteEventBox remoteEvent ->
-            ( model, Websocket.send (LiveData.encodeRemoteEvent remoteEvent) )
+            ( { model | nextEventId = model.nextEventId + 1 }
+            , Websocket.send (LiveData.encodeRemoteEvent model.nextEventId remoteEvent)
+            )
 
-        NewServerState liveState ->
-            ( { liveState = Just liveState
+        LiveStateFrameReceived frame ->
+            case LiveData.applyLiveStateFrame model.rawLiveState frame of
+                Ok raw ->
+                    case Json.Decode.decodeValue decodeLiveState (LiveData.encodeRawLiveState raw) of
+                        Ok liveState ->
+                            ( { model | liveState = Just liveState, rawLiveState = Just raw }
+                            , Cmd.none
+                            )
+
+                        Err _ ->
+                            ( { model | rawLiveState = Just raw }, Cmd.none )
+
+                Err _ ->
+                    ( model, Websocket.send LiveData.encodeResync )
+
+        RouteErrorReceived routeError ->
+            ( { model | routeError = Just routeError }, Cmd.none )
+
+        DismissRouteError ->
+            ( { model | routeError = Nothing }, Cmd.none )
+
+        EventReplyReceived reply ->
+            case LiveData.eventResultNotification reply.result of
+                Just notification ->
+                    update (NotificationReceived notification) model
+
+                Nothing ->
+                    ( model, Cmd.none )
+
+        NotificationReceived notification ->
+            let
+                id =
+                    model.nextNotificationId
+            in
+            ( { model
+                | notifications = model.notifications ++ [ ( id, notification ) ]
+                , nextNotificationId = id + 1
               }
+            , Process.sleep notificationTimeout |> Task.perform (\_ -> DismissNotification id)
+            )
+
+        DismissNotification id ->
+            ( { model | notifications = List.filter (\( other, _ ) -> other /= id) model.notifications }
             , Cmd.none
             )
 
@@ -77,16 +158,55 @@ view model =
             Page.Wedding.view liveState |> Html.map (LiveData.WeddingRemoteEventWrapper >> RemoteEventBox)
 
 
+viewRouteError : Maybe LiveData.RouteError -> Html Msg
+viewRouteError maybeError =
+    case maybeError of
+        Nothing ->
+            Html.div [] []
+
+        Just routeError ->
+            Html.div [ class "text-center bg-red-300 p-1 sm:p-2 m-1" ]
+                [ Html.text (LiveData.routeErrorText routeError)
+                , Html.button [ class "px-2 font-bold", onClick DismissRouteError ] [ Html.text "×" ]
+                ]
+
+
+viewNotifications : List ( Int, LiveData.Notification ) -> Html Msg
+viewNotifications notifications =
+    Html.div [ class "fixed bottom-0 right-0 m-2 space-y-2" ]
+        (List.map viewNotification notifications)
+
+
+viewNotification : ( Int, LiveData.Notification ) -> Html Msg
+viewNotification ( id, notification ) =
+    Html.div [ class ("p-2 rounded shadow " ++ LiveData.notificationColor notification) ]
+        [ Html.text notification.text
+        , Html.button [ class "px-2 font-bold", onClick (DismissNotification id) ] [ Html.text "×" ]
+        ]
+
+
 sandboxLiveStateParser : Json.Decode.Value -> Msg
 sandboxLiveStateParser value =
-    case Json.Decode.decodeValue decodeLiveState value of
-        Ok server ->
-            NewServerState server
+    case Json.Decode.decodeValue decodeServerFrame value of
+        Ok msg ->
+            msg
 
         Err e ->
             BrokenLiveState (Json.Decode.errorToString e)
 
 
+{-| Everything the server can send us over the websocket.
+-}
+decodeServerFrame : Json.Decode.Decoder Msg
+decodeServerFrame =
+    Json.Decode.oneOf
+        [ Json.Decode.map LiveStateFrameReceived LiveData.decodeLiveStateFrame
+        , Json.Decode.map RouteErrorReceived LiveData.decodeRouteError
+        , Json.Decode.map NotificationReceived LiveData.decodeNotification
+        , Json.Decode.map EventReplyReceived LiveData.decodeEventReply
+        ]
+
+
 {-| Overall live state. Should be automatically generated eventually.
 -}
 type LiveState
@@ -10,24 +10,65 @@ import PompData exposing (..)
 view : PompPlayerView -> Html PompEvent
 view model =
     div []
-        [ viewWinner model.winner
-        , viewMyInventory model.myInventory
-        , viewMarketplace model.market
+        [ viewCountdown model.secondsLeft
+        , viewRanking model.ranking
+        , viewMaybeInventory model.myInventory
+        , viewMyReserved model.myReserved
+        , viewMarketplace model.market model.deckSizes
+        , viewPatrons model.patrons
+        , viewGamble model.gamble
         , viewOthers model.others
+        , div [ class "m-1 text-right text-gray-500" ] [ text ("Seed " ++ String.fromInt model.seed) ]
         ]
 
 
-viewWinner : Maybe String -> Html a
-viewWinner maybeWinner =
-    case maybeWinner of
+viewCountdown : Maybe Int -> Html a
+viewCountdown secondsLeft =
+    case secondsLeft of
         Nothing ->
             div [] []
 
-        Just winner ->
-            div [ class "text-center bg-yellow-300 p-1 sm:p-2 font-bold m-1" ]
-                [ text winner
-                , text " won the game!"
-                ]
+        Just seconds ->
+            div [ class "text-center bg-yellow-100 p-1 sm:p-2 m-1" ]
+                [ text ("The game ends in " ++ String.fromInt seconds ++ " seconds.") ]
+
+
+{-| The ranking is empty while the game is running.
+-}
+viewRanking : List Rank -> Html a
+viewRanking ranking =
+    if List.isEmpty ranking then
+        div [] []
+
+    else
+        div [ class "bg-yellow-300 p-1 sm:p-2 m-1" ]
+            (div [ class "text-center font-bold" ] [ text "The game is over!" ]
+                :: List.map viewRank ranking
+            )
+
+
+viewRank : Rank -> Html a
+viewRank rank =
+    div [ class "flex flex-row" ]
+        [ div [ class "basis-1/12 font-bold" ] [ text (String.fromInt rank.place ++ ".") ]
+        , div [ class "basis-5/12" ] [ text rank.name ]
+        , div [ class "basis-2/12" ] [ text (String.fromInt rank.points ++ " Points") ]
+        , div [ class "basis-2/12" ] [ text (String.fromInt rank.cards ++ " Cards") ]
+        , div [ class "basis-2/12" ] [ text (String.fromInt rank.elements ++ " Elements") ]
+        ]
+
+
+{-| Without an inventory you are spectating. Everybody is one of the others then.
+-}
+viewMaybeInventory : Maybe PlayerInventoryView -> Html PompEvent
+viewMaybeInventory maybeInventory =
+    case maybeInventory of
+        Just inventory ->
+            viewMyInventory inventory
+
+        Nothing ->
+            div [ class "m-1 bg-gray-100 p-1 sm:p-2 text-center text-gray-500" ]
+                [ text "You are spectating this game." ]
 
 
 viewMyInventory : PlayerInventoryView -> Html PompEvent
@@ -51,19 +92,95 @@ viewMyInventory inventory =
             , button [ onClick (Buy Earth), class "basis-1/5 text-center p-1 bg-amber-200 hover:bg-amber-300 active:bg-amber-400 border-amber-500 border-2" ]
                 [ text (String.fromInt inventory.elements.earth ++ " (+" ++ String.fromInt inventory.discount.earth ++ ") Earth")
                 ]
-            , button [ onClick (Buy Chaos), class "basis-1/5 text-center p-1 bg-purple-200 hover:bg-purple-300 active:bg-purple-400 border-purple-500 border-2" ]
-                [ text (String.fromInt inventory.elements.chaos ++ " (+" ++ String.fromInt inventory.discount.chaos ++ ") Chaos")
+            , div [ class "basis-1/5 text-center p-1 bg-purple-200 border-purple-500 border-2" ]
+                [ text (viewWildcards inventory)
                 ]
             ]
+        , viewIncome inventory
+        ]
+
+
+{-| Only you can buy these cards, the others just see how many you have.
+-}
+viewMyReserved : List Card -> Html PompEvent
+viewMyReserved cards =
+    if List.isEmpty cards then
+        div [] []
+
+    else
+        div [ class "m-1 bg-gray-100 p-1 sm:p-2 sm:space-y-1" ]
+            [ div [ class "font-bold text-center" ] [ text "Reserved" ]
+            , div [ class "flex flex-row sm:space-x-1" ]
+                (List.map (\card -> div [ class "flex-1 grid" ] [ viewCard (BuyReserved card.id) card ]) cards)
+            ]
+
+
+{-| Patrons give bonus points to the first player with enough discount.
+-}
+viewPatrons : List Patron -> Html a
+viewPatrons patrons =
+    div [ class "m-1 bg-gray-100 p-1 sm:p-2 sm:space-y-1" ]
+        [ div [ class "font-bold text-center" ] [ text "Patrons" ]
+        , div [ class "flex flex-row sm:space-x-1" ] (List.map viewPatron patrons)
+        ]
+
+
+viewPatron : Patron -> Html a
+viewPatron patron =
+    let
+        owner =
+            case patron.owner of
+                Just name ->
+                    "Taken by " ++ name
+
+                Nothing ->
+                    "Free"
+    in
+    div [ class "flex-1 p-1 sm:p-2 border-gray-300 border-2" ]
+        [ div [ class "text-right" ] [ text (String.fromInt patron.points) ]
+        , viewCost patron.requirement
+        , div [ class "text-gray-500" ] [ text owner ]
+        ]
+
+
+{-| The bundle grows over time until someone buys it.
+-}
+viewGamble : GambleView -> Html PompEvent
+viewGamble gamble =
+    div [ class "m-1 bg-gray-100 p-1 sm:p-2" ]
+        [ button [ onClick Gamble, class "w-full text-center p-1 bg-yellow-200 hover:bg-yellow-300 active:bg-yellow-400 border-yellow-500 border-2" ]
+            [ text ("Gamble: " ++ String.fromInt gamble.size ++ " random elements for " ++ String.fromInt gamble.price ++ " Energy") ]
         ]
 
 
-viewMarketplace : List (Maybe Card) -> Html PompEvent
-viewMarketplace cards =
+{-| Chaos can't be bought. Chaos cards give a wildcard discount instead, which
+works for any color. Chaos elements from reserving are wildcards that are used
+up when paying.
+-}
+viewWildcards : PlayerInventoryView -> String
+viewWildcards inventory =
+    String.fromInt inventory.elements.chaos ++ " (+" ++ String.fromInt inventory.discount.chaos ++ ") Chaos"
+
+
+{-| Each tier is one row of the market. The number of tiers and slots comes
+from the decks file of the server.
+-}
+viewMarketplace : List (List (Maybe Card)) -> List Int -> Html PompEvent
+viewMarketplace tiers deckSizes =
     div [ class "m-1 bg-gray-100 p-1 sm:p-2 sm:space-y-1" ]
-        [ div [ class "font-bold text-center" ] [ text "Marketplace" ]
-        , div [ class "grid sm:gap-1 grid-cols-5 grid-rows-3" ]
-            (List.map viewMaybeCard cards)
+        (div [ class "font-bold text-center" ] [ text "Marketplace" ]
+            :: List.map2 viewMarketRow tiers deckSizes
+        )
+
+
+{-| The deck size tells how many cards can still refill this row.
+-}
+viewMarketRow : List (Maybe Card) -> Int -> Html PompEvent
+viewMarketRow cards deckSize =
+    div []
+        [ div [ class "flex flex-row sm:space-x-1" ]
+            (List.map (\card -> div [ class "flex-1 grid" ] [ viewMaybeCard card ]) cards)
+        , div [ class "text-right text-xs text-gray-500" ] [ text (String.fromInt deckSize ++ " cards left") ]
         ]
 
 
@@ -71,23 +188,76 @@ viewMaybeCard : Maybe Card -> Html PompEvent
 viewMaybeCard maybeCard =
     case maybeCard of
         Just card ->
-            viewCard card
+            div [ class "grid" ]
+                [ viewCard (BuyCard card.id) card
+                , button [ onClick (Reserve card.id), class "text-sm text-gray-500 hover:bg-gray-200" ] [ text "Reserve" ]
+                ]
 
         Nothing ->
             div [ class "text-center p-1 sm:p-2 border-gray-300 border-2" ] [ text "Sold" ]
 
 
-viewCard : Card -> Html PompEvent
-viewCard card =
-    button [ class "p-1 sm:p-2 border-gray-300 border-2", onClick (BuyCard card.id) ]
+viewCard : PompEvent -> Card -> Html PompEvent
+viewCard buy card =
+    button [ class "p-1 sm:p-2 border-gray-300 border-2", onClick buy ]
         [ div [ class "flex flex-row" ]
             [ div [ class "basis-1/2" ] [ text (colorName card.color) ]
             , div [ class "basis-1/2 text-right" ] [ text (String.fromInt card.points) ]
             ]
         , viewCardCost card
+        , viewPower card.power
         ]
 
 
+viewPower : Maybe Power -> Html a
+viewPower maybePower =
+    case maybePower of
+        Nothing ->
+            div [] []
+
+        Just power ->
+            div [ class "text-left font-bold" ]
+                [ text (powerText power.effect)
+                , div [ class "font-normal text-gray-500" ] [ text ("down to " ++ String.fromInt power.minCost) ]
+                ]
+
+
+powerText : PowerEffect -> String
+powerText effect =
+    case effect of
+        FasterEnergy ->
+            "Faster energy"
+
+        ElementIncome color ->
+            "+1 " ++ colorName color ++ " income"
+
+
+{-| Shows how fast energy and the income of power cards come in.
+-}
+viewIncome : PlayerInventoryView -> Html a
+viewIncome inventory =
+    let
+        income =
+            List.filter (\( _, amount ) -> amount > 0)
+                [ ( "Fire", inventory.income.fire )
+                , ( "Plant", inventory.income.plant )
+                , ( "Water", inventory.income.water )
+                , ( "Earth", inventory.income.earth )
+                , ( "Chaos", inventory.income.chaos )
+                ]
+                |> List.map (\( name, amount ) -> String.fromInt amount ++ " " ++ name)
+
+        incomeText =
+            if List.isEmpty income then
+                ""
+
+            else
+                ", income: " ++ String.join ", " income
+    in
+    div [ class "text-center text-gray-500" ]
+        [ text (String.fromInt inventory.energyPerMinute ++ " Energy per minute" ++ incomeText) ]
+
+
 colorName : ElementColor -> String
 colorName color =
     case color of
@@ -107,12 +277,17 @@ colorName color =
             "Chaos"
 
 
+viewCardCost : Card -> Html a
+viewCardCost card =
+    viewCost card.cost
+
+
 {-| This shows how much a card costs. All elements that don't have to be paid
 at all are left out from the listing. Instead they are padded in the front
 with empty lines.
 -}
-viewCardCost : Card -> Html a
-viewCardCost card =
+viewCost : ElementVector -> Html a
+viewCost elements =
     let
         s name cardCost =
             if cardCost == 0 then
@@ -123,11 +298,11 @@ viewCardCost card =
 
         cost =
             List.concat
-                [ s "Fire" card.cost.fire
-                , s "Plant" card.cost.plant
-                , s "Water" card.cost.water
-                , s "Earth" card.cost.earth
-                , s "Chaos" card.cost.chaos
+                [ s "Fire" elements.fire
+                , s "Plant" elements.plant
+                , s "Water" elements.water
+                , s "Earth" elements.earth
+                , s "Chaos" elements.chaos
                 ]
 
         costDivList =
@@ -149,12 +324,37 @@ viewOthers others =
         (List.map viewOther others)
 
 
+awayLabel : Bool -> String
+awayLabel away =
+    if away then
+        " (away)"
+
+    else
+        ""
+
+
+viewReservedTiers : List Int -> Html a
+viewReservedTiers tiers =
+    if List.isEmpty tiers then
+        div [] []
+
+    else
+        div [ class "text-center text-gray-500" ]
+            [ text
+                (String.fromInt (List.length tiers)
+                    ++ " reserved (tier "
+                    ++ String.join ", " (List.map (\tier -> String.fromInt (tier + 1)) tiers)
+                    ++ ")"
+                )
+            ]
+
+
 viewOther : PlayerInventoryView -> Html a
 viewOther other =
     div [ class "m-1 bg-gray-100 p-1 sm:p-2" ]
         [ div [ class "flex flex-row sm:space-x-1" ]
             [ div [ class "basis-1/4 text-center p-1" ] [ text (String.fromInt other.points ++ " Points") ]
-            , div [ class "basis-2/4 text-center p-1 font-bold" ] [ text other.name ]
+            , div [ class "basis-2/4 text-center p-1 font-bold" ] [ text (other.name ++ awayLabel other.away) ]
             , div [ class "basis-1/4 text-center p-1" ] [ text (String.fromInt other.energy ++ " Energy") ]
             ]
         , div [ class "flex flex-row sm:space-x-1" ]
@@ -171,7 +371,9 @@ viewOther other =
                 [ text (String.fromInt other.elements.earth ++ " (+" ++ String.fromInt other.discount.earth ++ ") Earth")
                 ]
             , div [ class "basis-1/5 text-center p-1 bg-purple-200 border-purple-500 border-2" ]
-                [ text (String.fromInt other.elements.chaos ++ " (+" ++ String.fromInt other.discount.chaos ++ ") Chaos")
+                [ text (viewWildcards other)
                 ]
             ]
+        , viewIncome other
+        , viewReservedTiers other.reservedTiers
         ]
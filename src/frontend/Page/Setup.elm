@@ -2,9 +2,9 @@ module Page.Setup exposing (view)
 
 import FontAwesome.Icon exposing (viewIcon)
 import FontAwesome.Solid as FA
-import Html exposing (Html, button, div, h1, p, text)
-import Html.Attributes exposing (class, disabled)
-import Html.Events exposing (onClick)
+import Html exposing (Html, button, div, h1, input, p, text)
+import Html.Attributes exposing (class, disabled, placeholder, type_, value)
+import Html.Events exposing (onClick, onInput)
 import LiveData exposing (..)
 
 
@@ -13,12 +13,14 @@ view model =
     div [ class "p-4" ]
         [ h1 [ class "text-xl pb-2" ] [ text "Set up a Pomp Game" ]
         , playerTable model
-        , if model.myIndex == 0 then
+        , if isHost model then
             startGameSection model
 
           else
             div [ class "pt-2" ]
-                [ text "Please wait for the game to start"
+                [ viewSeed model.seed
+                , viewRules model.rules
+                , text "Please wait for the game to start"
                 ]
         ]
 
@@ -30,18 +32,47 @@ view model =
 
 playerTable : SetupLiveState -> Html SetupRemoteEvent
 playerTable model =
-    div [] (List.indexedMap (viewPlayerData model.myIndex) model.data)
+    div [] (List.indexedMap (viewPlayerData (isHost model) model.myIndex) model.data)
 
 
-viewPlayerData : Int -> Int -> PlayerSetupData -> Html SetupRemoteEvent
-viewPlayerData myIndex dataIndex data =
+{-| The host is the first player that is not a bot, the same as on the server.
+-}
+isHost : SetupLiveState -> Bool
+isHost model =
+    model.data
+        |> List.indexedMap Tuple.pair
+        |> List.filter (\( _, data ) -> data.bot == Nothing)
+        |> List.head
+        |> Maybe.map Tuple.first
+        |> (==) (Just model.myIndex)
+
+
+viewPlayerData : Bool -> Int -> Int -> PlayerSetupData -> Html SetupRemoteEvent
+viewPlayerData amHost myIndex dataIndex data =
     if myIndex == dataIndex then
         myPlayerData data
 
+    else if amHost && data.bot /= Nothing then
+        botData dataIndex data
+
     else
         otherPlayerData data
 
 
+{-| The host can remove bots again.
+-}
+botData : Int -> PlayerSetupData -> Html SetupRemoteEvent
+botData index data =
+    div [ class "flex flex-row" ]
+        [ div [ class "basis-3/4 space-x-2" ]
+            [ text data.name
+            , button [ class "px-1 bg-gray-300 hover:bg-gray-600 rounded", onClick (RemoveBot index) ] [ text "Remove" ]
+            ]
+        , div [ class "basis-1/4" ]
+            [ readyLabel data.isReady ]
+        ]
+
+
 myPlayerData : PlayerSetupData -> Html SetupRemoteEvent
 myPlayerData data =
     div [ class "flex flex-row" ]
@@ -97,7 +128,103 @@ readyLabelContent isReady =
 
 startGameSection : SetupLiveState -> Html SetupRemoteEvent
 startGameSection model =
-    startGameButton model
+    div []
+        [ addBotButton
+        , seedInput model.seed
+        , rulesInput model.rules
+        , startGameButton model
+        ]
+
+
+addBotButton : Html SetupRemoteEvent
+addBotButton =
+    div [ class "pt-2" ]
+        [ button [ class "p-1 bg-gray-300 hover:bg-gray-600 rounded", onClick (AddBot "Greedy") ] [ text "Add Greedy Bot" ] ]
+
+
+{-| Playing with the same seed gives the same cards. Leave it empty for a
+random seed.
+-}
+seedInput : Maybe Int -> Html SetupRemoteEvent
+seedInput seed =
+    div [ class "pt-2 space-x-2" ]
+        [ text "Seed"
+        , input
+            [ type_ "number"
+            , placeholder "Random"
+            , class "px-1 border-2 border-gray-300"
+            , value (Maybe.map String.fromInt seed |> Maybe.withDefault "")
+            , onInput (String.toInt >> SetSeed)
+            ]
+            []
+        ]
+
+
+{-| Only the host can change the rules. Inputs that are not a number keep the
+old value, the server rejects values that would break the game.
+-}
+rulesInput : PompRules -> Html SetupRemoteEvent
+rulesInput rules =
+    div [ class "pt-2" ]
+        [ numberInput "Points to win" (Just rules.scoreToWin) (\n -> { rules | scoreToWin = Maybe.withDefault rules.scoreToWin n })
+        , numberInput "Seconds to catch up" (Just rules.graceSeconds) (\n -> { rules | graceSeconds = Maybe.withDefault rules.graceSeconds n })
+        , numberInput "Ticks per second" (Just rules.ticksPerSecond) (\n -> { rules | ticksPerSecond = Maybe.withDefault rules.ticksPerSecond n })
+        , numberInput "Ticks per energy" (Just rules.ticksPerEnergy) (\n -> { rules | ticksPerEnergy = Maybe.withDefault rules.ticksPerEnergy n })
+        , numberInput "Market slots per row" rules.marketSlots (\n -> { rules | marketSlots = n })
+        , numberInput "Deck size in %" (Just rules.deckPercent) (\n -> { rules | deckPercent = Maybe.withDefault rules.deckPercent n })
+        , div [ class "space-x-2" ]
+            [ text "Refill empty decks"
+            , button [ class "px-1 bg-gray-300 hover:bg-gray-600 rounded", onClick (SetRules { rules | refillDecks = not rules.refillDecks }) ]
+                [ text (onOff rules.refillDecks) ]
+            ]
+        ]
+
+
+onOff : Bool -> String
+onOff on =
+    if on then
+        "On"
+
+    else
+        "Off"
+
+
+numberInput : String -> Maybe Int -> (Maybe Int -> PompRules) -> Html SetupRemoteEvent
+numberInput label current update =
+    div [ class "space-x-2" ]
+        [ text label
+        , input
+            [ type_ "number"
+            , placeholder "From decks"
+            , class "px-1 border-2 border-gray-300"
+            , value (Maybe.map String.fromInt current |> Maybe.withDefault "")
+            , onInput (String.toInt >> update >> SetRules)
+            ]
+            []
+        ]
+
+
+viewRules : PompRules -> Html a
+viewRules rules =
+    div []
+        [ p [] [ text ("Points to win: " ++ String.fromInt rules.scoreToWin) ]
+        , p [] [ text ("Seconds to catch up: " ++ String.fromInt rules.graceSeconds) ]
+        , p [] [ text ("Ticks per second: " ++ String.fromInt rules.ticksPerSecond) ]
+        , p [] [ text ("Ticks per energy: " ++ String.fromInt rules.ticksPerEnergy) ]
+        , p [] [ text ("Market slots per row: " ++ (Maybe.map String.fromInt rules.marketSlots |> Maybe.withDefault "From decks")) ]
+        , p [] [ text ("Deck size: " ++ String.fromInt rules.deckPercent ++ "%") ]
+        , p [] [ text ("Refill empty decks: " ++ onOff rules.refillDecks) ]
+        ]
+
+
+viewSeed : Maybe Int -> Html a
+viewSeed seed =
+    case seed of
+        Just s ->
+            p [] [ text ("Seed " ++ String.fromInt s) ]
+
+        Nothing ->
+            p [] [ text "Random seed" ]
 
 
 startGameButton : SetupLiveState -> Html SetupRemoteEvent
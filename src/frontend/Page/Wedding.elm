@@ -141,9 +141,22 @@ activeQuestionView id question =
             , questionStateButton question.state "Jeremias" id (Answered Groom)
             , questionStateButton question.state "Konflikt" id ConflictAnswer
             ]
+        , closeVotingLaterButton question.state id
         ]
 
 
+closeVotingLaterButton : QuestionState -> Int -> Element WeddingEvent
+closeVotingLaterButton currentState id =
+    if currentState == GuestsCanVote then
+        Input.button [ padding 5, Border.width 1 ]
+            { onPress = Just (CloseVotingLater id)
+            , label = Element.text "Abstimmung in 20 Sekunden beenden"
+            }
+
+    else
+        Element.none
+
+
 questionStateButton : QuestionState -> String -> Int -> QuestionState -> Element WeddingEvent
 questionStateButton currentState caption id targetState =
     if currentState == targetState then
@@ -1,11 +1,11 @@
 //! This module holds all the general framework types that should be used in user code.
 
-use std::{any::Any, fmt::Display, time::Duration};
+use std::{any::Any, fmt::Display, hash::Hash, time::Duration};
 
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 /** Identifier for players, this way we can play without accounts. */
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+#[derive(Hash, Eq, PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct UserUuid(String);
 
 impl Display for UserUuid {
@@ -34,6 +34,64 @@ impl UserUuid {
 
         None
     }
+
+    /// Made up uuid for players that never connect, like bots. It can't
+    /// collide with a real uuid, because those are always parsed.
+    pub fn synthetic(name: &str) -> Self {
+        UserUuid(format!("SYNTHETIC-{}", name))
+    }
+}
+
+/// Identifies one table of a game, e.g. the `3` in `/pomp/3`.
+pub type GameId = usize;
+
+/// Typed path parameters of a live route. The broker parses the `{...}`
+/// segments of a route pattern into this type and keeps one live state
+/// instance per distinct value.
+pub trait RouteParams: Sized + Clone + Eq + Hash + Send + 'static {
+    /// Parse the captured segments, in the order they appear in the pattern.
+    fn from_segments(segments: &[&str]) -> Option<Self>;
+}
+
+/// Routes without any parameters only ever have a single instance.
+impl RouteParams for () {
+    fn from_segments(segments: &[&str]) -> Option<Self> {
+        if segments.is_empty() {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+impl RouteParams for GameId {
+    fn from_segments(segments: &[&str]) -> Option<Self> {
+        match segments {
+            [id] => id.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Matches a path like "/pomp/3/setup" against a pattern like "/pomp/{id}/setup"
+/// and returns the captured segments if it matches.
+pub fn match_route<'a>(pattern: &str, path: &'a str) -> Option<Vec<&'a str>> {
+    let mut pattern_segments = pattern.split('/');
+    let mut path_segments = path.split('/');
+    let mut captures = Vec::new();
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return Some(captures),
+            (Some(p), Some(s)) if p.starts_with('{') && p.ends_with('}') => {
+                if s.is_empty() {
+                    return None;
+                }
+                captures.push(s);
+            }
+            (Some(p), Some(s)) if p == s => {}
+            _ => return None,
+        }
+    }
 }
 
 /// We don't want to expose the full actual `LiveState` or `SharedLiveState` to the
@@ -46,40 +104,91 @@ pub trait RemoteEvent: Sized {
     fn deserialize(s: &str) -> Result<Self, serde_json::Error>;
 }
 
-/// Variation of a `LiveState` that is shared between users.
-pub trait SharedLiveState: Default + Unpin + Any + 'static {
+/// Variation of a `LiveState` that is shared between users. It is written to
+/// disk as JSON, so it survives a restart of the server.
+pub trait SharedLiveState: Sized + Unpin + Any + Serialize + DeserializeOwned + 'static {
     // Each Game has a type of remote event that it handles.
     type Event: RemoteEvent;
     // As well as a type of live state that it sends to the frontend.
     type View: UserView;
+    // The path parameters of the route pattern, e.g. a `GameId`.
+    type Params: RouteParams;
+    // Payload of the timers this live state schedules with `LiveEffect::Schedule`.
+    // It is serialized into the event log when the timer fires.
+    type Timer: Any + Send + Clone + Serialize + DeserializeOwned;
+    // Reason why an event was rejected, this is sent back to the client.
+    type Rejection: Serialize + Send;
+
+    /// The route this live state is served on, e.g. "/pomp/{id}/setup".
+    /// Each `{...}` segment is parsed into `Self::Params`.
+    fn route_pattern() -> &'static str;
+
+    /// Creates the live state when its route is visited for the first time.
+    /// Return `None` if it can only be created with setup data through a
+    /// `LiveEffect::LiveRedirectInit`.
+    fn from_route(params: &Self::Params) -> Option<Self>;
 
     // Map to the live state that is sent to the frontend.
     fn user_view(&self, player: &UserUuid) -> Self::View;
 
     // Handle events. After every event the current state is send to all clients
     // so there is no need to think about this in this method.
-    fn process_remote_event(&mut self, event: Self::Event, sender: UserUuid) -> LiveEffect;
+    // Return a rejection if the event can't be processed, the sender is told
+    // why. A rejected event must leave the state unchanged.
+    fn process_remote_event(
+        &mut self,
+        event: Self::Event,
+        sender: UserUuid,
+    ) -> Result<LiveEffect, Self::Rejection>;
 
     /// Define how often this live state should process a tick.
     /// If you don't define it, you don't need to process ticks at all.
+    /// Once this returns `None`, ticks stop for good.
     fn tick_frequency(&self) -> Option<Duration> {
         None
     }
 
     /// Called every tick. If you don't set a tick frequency, this is never called.
     /// If you don't define this, it does nothing.
+    /// The returned effect is handled like the one of an event, so a tick can
+    /// also redirect everyone to a different route.
     fn process_tick(&mut self) -> LiveEffect {
         LiveEffect::None
     }
 
+    /// Called when a timer scheduled with `LiveEffect::Schedule` fires.
+    /// If you don't schedule timers, this is never called.
+    fn process_timer(&mut self, _payload: Self::Timer) -> LiveEffect {
+        LiveEffect::None
+    }
+
     // Add a player to the game.
     // This has a live effect, because a player may join a "preparation" page
     // a little bit too late. Then they would be redirected into the active
     // game as a spectator.
     fn join_user(&mut self, player: UserUuid) -> LiveEffect;
 
-    // ID used to differentiate this game from others.
-    // Will probably replace this by better routing later.
+    /// Called when the last connection of a player to this route is gone.
+    /// Players with several browser tabs only leave once all of them are closed.
+    /// If you don't define this, it does nothing.
+    fn leave_user(&mut self, _player: UserUuid) -> LiveEffect {
+        LiveEffect::None
+    }
+
+    /// How long a player may be disconnected before `leave_user` is called.
+    /// This way reloading the page does not count as leaving.
+    fn leave_grace_period(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Users that joined and did not leave yet. Nobody is connected to a live
+    /// state restored from disk, so these get the grace period to come back
+    /// before they leave. If you don't define this, nobody has to leave.
+    fn present_users(&self) -> Vec<UserUuid> {
+        Vec::new()
+    }
+
+    // ID used to tell the frontend which page to render.
     fn route_id() -> &'static str;
 }
 
@@ -90,4 +199,114 @@ pub enum LiveEffect {
     None,                                          // Equivalent to Cmd.none from Elm.
     LiveRedirectInit(String, Box<dyn Any + Send>), // Not sure if "Any" can be avoided here.
     LiveRedirect(String),                          // Like LiveRedirectInit, but without setup data.
+    Schedule(Timer),                               // Calls process_timer later.
+    CancelTimer(TimerId),                          // Nothing happens if the timer is not running.
+    Notify(Audience, Notification),                // Not part of the view, only seen once.
+    Batch(Vec<LiveEffect>),                        // Handled in order.
+}
+
+impl LiveEffect {
+    /// Calls `process_timer` with the payload once after the delay.
+    pub fn schedule_once<T: Any + Send>(id: TimerId, delay: Duration, payload: T) -> Self {
+        LiveEffect::Schedule(Timer {
+            id,
+            delay,
+            repeat: false,
+            payload: Box::new(payload),
+        })
+    }
+}
+
+/// Who should see a `Notification`.
+pub enum Audience {
+    User(UserUuid),
+    Users(Vec<UserUuid>),
+    Everyone,
+}
+
+impl Audience {
+    pub fn contains(&self, user: &UserUuid) -> bool {
+        match self {
+            Audience::User(uuid) => uuid == user,
+            Audience::Users(uuids) => uuids.contains(user),
+            Audience::Everyone => true,
+        }
+    }
+}
+
+/// An ephemeral message like a toast or a sound. Clients that connect later
+/// will never see it, put everything that must not get lost into the view.
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub text: String,
+    pub sound: Option<String>, // Name of a sound file the client plays.
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub enum NotificationLevel {
+    Info,
+    Success,
+    Warning,
+}
+
+impl Notification {
+    pub fn new(level: NotificationLevel, text: impl Into<String>) -> Self {
+        Notification {
+            level,
+            text: text.into(),
+            sound: None,
+        }
+    }
+}
+
+/// Names a timer so it can be cancelled. Scheduling a timer with an id that
+/// is already running replaces the old timer.
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub struct TimerId(pub String);
+
+/// A timer requested by the live state. The payload must be the `Timer` type
+/// of the live state, otherwise the timer is dropped with an error.
+pub struct Timer {
+    pub id: TimerId,
+    pub delay: Duration,
+    pub repeat: bool, // Fires every `delay` until it is cancelled.
+    pub payload: Box<dyn Any + Send>,
+}
+
+// Test module
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_route() {
+        assert_eq!(Some(vec![]), match_route("/wedding", "/wedding"));
+        assert_eq!(Some(vec!["3"]), match_route("/pomp/{id}", "/pomp/3"));
+        assert_eq!(
+            Some(vec!["3"]),
+            match_route("/pomp/{id}/setup", "/pomp/3/setup")
+        );
+        assert_eq!(None, match_route("/pomp/{id}", "/pomp/3/setup"));
+        assert_eq!(None, match_route("/pomp/{id}", "/pomp/"));
+        assert_eq!(None, match_route("/wedding", "/pomp/3"));
+    }
+
+    #[test]
+    fn test_route_params() {
+        assert_eq!(Some(3), GameId::from_segments(&["3"]));
+        assert_eq!(None, GameId::from_segments(&["three"]));
+        assert_eq!(None, <()>::from_segments(&["3"]));
+    }
+
+    #[test]
+    fn test_audience() {
+        let alice = UserUuid("alice".to_owned());
+        let bob = UserUuid("bob".to_owned());
+        assert!(Audience::User(alice.clone()).contains(&alice));
+        assert!(!Audience::User(alice.clone()).contains(&bob));
+        assert!(!Audience::Users(vec![]).contains(&bob));
+        assert!(Audience::Users(vec![alice, bob.clone()]).contains(&bob));
+        assert!(Audience::Everyone.contains(&bob));
+    }
 }
@@ -1,11 +1,19 @@
+mod bots;
+mod decks;
+mod event_log;
 mod game;
+mod persistence;
 mod pomp;
+mod routes;
 mod setup;
+mod simulate;
+mod view_sync;
 mod wedding;
 mod wedding_types;
 
 use std::any::Any;
 use std::collections::HashMap;
+use std::fmt::Display;
 use std::time::{Duration, Instant};
 
 use actix::prelude::*;
@@ -13,17 +21,25 @@ use actix::{Actor, Handler, Message, StreamHandler, Supervised, SystemService};
 use actix_web::{web, App, Error, HttpRequest, HttpResponse, HttpServer};
 use actix_web_actors::ws;
 
-use game::{RemoteEvent, SharedLiveState, UserUuid, UserView};
-use lazy_static::lazy_static;
-use log::{debug, error, info, trace};
-use regex::Regex;
-use serde::Serialize;
+use event_log::{EventLog, LogEntry};
+use game::{RemoteEvent, RouteParams, SharedLiveState, UserUuid, UserView};
+use log::{debug, error, info, trace, warn};
+use persistence::{Snapshot, SnapshotStore};
+use routes::RouteTable;
+use serde::{Deserialize, Serialize};
+use view_sync::ViewSync;
 
 /// How often heartbeat pings are sent
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 /// How long before lack of client response causes a timeout
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 
+/// How often live states are written to disk.
+const PERSIST_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Route that is shown when the client navigates to "/".
+const INDEX_ROUTE: &str = "/wedding";
+
 /// The `WebsocketActor` takes care of the websocket connection. It forwards the
 /// current `UserView` (LiveState) to the client. It also tracks which
 /// `LiveActor` it is connected to and will send `RemoteEvent`s to the correct
@@ -36,35 +52,21 @@ const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
 struct WebsocketActor {
     hb: Instant,
     uuid: UserUuid,
-    backing_actor: Recipient<SharedLiveActorMessage>,
-    last_send: String,
+    // Stays empty until the client tells us where it wants to navigate.
+    backing_actor: Option<Recipient<SharedLiveActorMessage>>,
+    // The view the client has right now, patches are computed against it.
+    views: ViewSync,
 }
 
 impl Actor for WebsocketActor {
     type Context = ws::WebsocketContext<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        // Register self to get updates to the game state
-        let result = self
-            .backing_actor
-            .do_send(SharedLiveActorMessage::Subscribe(
-                ctx.address(),
-                self.uuid.clone(),
-            ));
-        if result.is_err() {
-            error!("Could not subscribe to backing actor.");
-        }
         self.hb(ctx);
     }
 
     fn stopped(&mut self, ctx: &mut Self::Context) {
-        // Unregister self
-        let result = self
-            .backing_actor
-            .do_send(SharedLiveActorMessage::Unsubscribe(ctx.address()));
-        if result.is_err() {
-            error!("Could not unsubscribe from backing actor.");
-        }
+        self.unsubscribe(ctx);
     }
 }
 
@@ -85,22 +87,147 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for WebsocketActor {
     }
 }
 
+/// Messages the client sends over the websocket.
+#[derive(Deserialize)]
+enum ClientMessage {
+    /// The client wants to see a different route, e.g. on page load or when
+    /// the browser history changes.
+    Navigate(String),
+    /// A `RemoteEvent` for the live state behind the current route.
+    Event(EventEnvelope),
+    /// The client could not apply a patch and needs a full snapshot.
+    Resync,
+}
+
+/// A `RemoteEvent` with an optional id chosen by the client. The server
+/// answers with an `EventReply` carrying the same id.
+#[derive(Deserialize)]
+struct EventEnvelope {
+    id: Option<u64>,
+    event: serde_json::Value,
+}
+
+/// Tells the client which route it is looking at now, so it can update the
+/// browser url after a `LiveRedirect`.
+#[derive(Serialize)]
+struct NavigatedFrame<'a> {
+    navigated: &'a str,
+}
+
 impl WebsocketActor {
-    fn handle_text(&mut self, msg: String, _ctx: &mut <WebsocketActor as Actor>::Context) {
-        // We can not decode the String into a structure directly inside the
+    fn handle_text(&mut self, msg: String, ctx: &mut <WebsocketActor as Actor>::Context) {
+        let msg = match serde_json::from_str(&msg) {
+            Ok(msg) => msg,
+            Err(_) => {
+                debug!("Could not decode client message: {}", msg);
+                return;
+            }
+        };
+        match msg {
+            ClientMessage::Navigate(route) => self.navigate(route, ctx),
+            ClientMessage::Event(envelope) => self.forward_event(envelope, ctx),
+            ClientMessage::Resync => self.send_snapshot(ctx),
+        }
+    }
+
+    fn forward_event(
+        &mut self,
+        envelope: EventEnvelope,
+        ctx: &mut <WebsocketActor as Actor>::Context,
+    ) {
+        let backing_actor = match &self.backing_actor {
+            Some(backing_actor) => backing_actor,
+            None => {
+                debug!("Dropping client side event, client has not navigated yet.");
+                return;
+            }
+        };
+        // We can not decode the event into a structure directly inside the
         // WebsocketActor, because only the GameActor or LiveActor knows about
         // the right type to deserialize into.
-        let result = self
-            .backing_actor
-            .do_send(SharedLiveActorMessage::ClientSideEvent {
-                event: msg,
-                sender: self.uuid.clone(),
-            });
+        let result = backing_actor.do_send(SharedLiveActorMessage::ClientSideEvent {
+            id: envelope.id,
+            event: envelope.event.to_string(),
+            sender: self.uuid.clone(),
+            socket: ctx.address(),
+        });
         if result.is_err() {
             error!("Could not send client side event to backing actor.");
         }
     }
 
+    /// Resolve the route with the broker and switch over to the new live actor.
+    /// Other messages from the client wait until this is done, so events are
+    /// never sent to the route we are leaving.
+    fn navigate(&mut self, route: String, ctx: &mut <WebsocketActor as Actor>::Context) {
+        let route = if route == "/" {
+            INDEX_ROUTE.to_owned()
+        } else {
+            route
+        };
+        debug!("Client navigates to {}", route);
+        let request = LiveRouteBroker::from_registry().send(RouteResolution(route.clone()));
+        ctx.wait(request.into_actor(self).map(move |result, act, ctx| {
+            match result.unwrap_or(Err(RouteError::MailboxClosed)) {
+                Ok(new_ref) => {
+                    act.unsubscribe(ctx);
+                    act.subscribe(new_ref, &route, ctx);
+                }
+                Err(error) => {
+                    debug!("Could not navigate to {}: {}", route, error);
+                    act.send_route_error(&ReportRouteError { route, error }, ctx);
+                }
+            }
+        }));
+    }
+
+    /// Start receiving updates from a new live actor.
+    fn subscribe(
+        &mut self,
+        backing_actor: Recipient<SharedLiveActorMessage>,
+        route: &str,
+        ctx: &mut <WebsocketActor as Actor>::Context,
+    ) {
+        let result = backing_actor.do_send(SharedLiveActorMessage::Subscribe(
+            ctx.address(),
+            self.uuid.clone(),
+        ));
+        if result.is_err() {
+            error!("Could not subscribe to backing actor.");
+        }
+        self.backing_actor = Some(backing_actor);
+        self.views.reset();
+
+        let frame = NavigatedFrame { navigated: route };
+        ctx.text(serde_json::to_string(&frame).expect("Serde error while encoding to json."));
+    }
+
+    /// Send the full view we have for the client. This starts a new chain of
+    /// patches.
+    fn send_snapshot(&mut self, ctx: &mut <WebsocketActor as Actor>::Context) {
+        if let Some(frame) = self.views.snapshot() {
+            ctx.text(frame);
+        }
+    }
+
+    fn send_route_error(
+        &mut self,
+        route_error: &ReportRouteError,
+        ctx: &mut <WebsocketActor as Actor>::Context,
+    ) {
+        let frame = RouteErrorFrame { route_error };
+        ctx.text(serde_json::to_string(&frame).expect("Serde error while encoding to json."));
+    }
+
+    fn unsubscribe(&mut self, ctx: &mut <WebsocketActor as Actor>::Context) {
+        if let Some(backing_actor) = self.backing_actor.take() {
+            let result = backing_actor.do_send(SharedLiveActorMessage::Unsubscribe(ctx.address()));
+            if result.is_err() {
+                error!("Could not unsubscribe from backing actor.");
+            }
+        }
+    }
+
     /// Heartbeat handler that will kill the process if the client dies.
     fn hb(&self, ctx: &mut <Self as Actor>::Context) {
         ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
@@ -116,7 +243,6 @@ impl WebsocketActor {
 }
 
 /// The Live Actor tells the Websocket about the game state.
-#[derive(Serialize)]
 struct UpdateLiveState<T: UserView> {
     route: &'static str,
     data: T,
@@ -132,20 +258,77 @@ impl<T: UserView> Handler<UpdateLiveState<T>> for WebsocketActor {
     fn handle(&mut self, msg: UpdateLiveState<T>, ctx: &mut ws::WebsocketContext<WebsocketActor>) {
         trace!("Sending update to client.");
 
-        let text = serde_json::to_string(&msg).expect("Serde error while encoding to json.");
-        // Check if there were any changes
-        if self.last_send == text {
-            // Nothing to do, skip this update.
-            return;
+        let view = serde_json::to_value(&msg.data).expect("Serde error while encoding to json.");
+        if let Some(frame) = self.views.update(msg.route, view) {
+            ctx.text(frame);
         }
+    }
+}
 
-        // TODO: Ideally we would only send the changes to reduce trafic.
-        self.last_send = text.clone();
-        ctx.text(text);
+/// The Live Actor wants to show a toast or play a sound. This is sent as its
+/// own frame and is not part of the `UserView`.
+struct ShowNotification(game::Notification);
+
+impl Message for ShowNotification {
+    type Result = ();
+}
+
+#[derive(Serialize)]
+struct NotificationFrame<'a> {
+    notification: &'a game::Notification,
+}
+
+impl Handler<ShowNotification> for WebsocketActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ShowNotification, ctx: &mut ws::WebsocketContext<WebsocketActor>) {
+        let frame = NotificationFrame {
+            notification: &msg.0,
+        };
+        ctx.text(serde_json::to_string(&frame).expect("Serde error while encoding to json."));
     }
 }
 
-struct PerformLiveRedirect(Recipient<SharedLiveActorMessage>);
+/// The Live Actor tells the Websocket what happened to a client side event.
+/// Events without an id are only answered when they are not accepted.
+#[derive(Serialize)]
+struct EventReply<R: Serialize> {
+    id: Option<u64>,
+    result: EventResult<R>,
+}
+
+#[derive(Serialize)]
+enum EventResult<R: Serialize> {
+    Ack,
+    Rejected(R),
+    Malformed(String), // The event could not be decoded for the route.
+}
+
+impl<R: Serialize> Message for EventReply<R> {
+    type Result = ();
+}
+
+#[derive(Serialize)]
+struct EventReplyFrame<'a, R: Serialize> {
+    reply: &'a EventReply<R>,
+}
+
+impl<R: Serialize> Handler<EventReply<R>> for WebsocketActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: EventReply<R>, ctx: &mut ws::WebsocketContext<WebsocketActor>) {
+        let frame = EventReplyFrame { reply: &msg };
+        ctx.text(serde_json::to_string(&frame).expect("Serde error while encoding to json."));
+    }
+}
+
+/// Sent by a live actor that moves all its subscribers to another route.
+/// The live actor has already dropped the subscription on its side, but the
+/// socket may have moved on to yet another actor in the meantime.
+struct PerformLiveRedirect {
+    route: String,
+    backing_actor: Recipient<SharedLiveActorMessage>,
+}
 
 impl Message for PerformLiveRedirect {
     type Result = ();
@@ -155,40 +338,46 @@ impl Handler<PerformLiveRedirect> for WebsocketActor {
     type Result = ();
 
     fn handle(&mut self, msg: PerformLiveRedirect, ctx: &mut ws::WebsocketContext<WebsocketActor>) {
-        self.backing_actor = msg.0;
-        let result = self
-            .backing_actor
-            .do_send(SharedLiveActorMessage::Subscribe(
-                ctx.address(),
-                self.uuid.clone(),
-            ));
-        if result.is_err() {
-            error!("Could not subscribe to backing actor. (Called for redirect)");
-        }
+        self.unsubscribe(ctx);
+        self.subscribe(msg.backing_actor, &msg.route, ctx);
     }
 }
 
+/// Tells the client that navigating to a route failed. The client stays on
+/// the route it was on before.
+#[derive(Serialize)]
+struct ReportRouteError {
+    route: String,
+    error: RouteError,
+}
+
+impl Message for ReportRouteError {
+    type Result = ();
+}
+
+impl Handler<ReportRouteError> for WebsocketActor {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReportRouteError, ctx: &mut ws::WebsocketContext<WebsocketActor>) {
+        self.send_route_error(&msg, ctx);
+    }
+}
+
+#[derive(Serialize)]
+struct RouteErrorFrame<'a> {
+    route_error: &'a ReportRouteError,
+}
+
 /// Sets up a websocket connection ensuring there is a uuid.
+/// The client then tells us which route to show with a `ClientMessage::Navigate`.
 async fn websocket_connect(req: HttpRequest, stream: web::Payload) -> Result<HttpResponse, Error> {
     if let Some(uuid) = UserUuid::from_query_string(req.query_string()) {
-        let router = LiveRouteBroker::from_registry();
-        let m = RouteResolution("/wedding".to_owned());
-        let addr = router
-            .send(m)
-            .await
-            .map_err(|_| {
-                actix_web::error::ErrorInternalServerError(
-                    "Internal Server Error in the actor system. (Mailbox Error)",
-                )
-            })?
-            .ok_or_else(|| actix_web::error::ErrorNotFound("Route not found."))?;
-
         let resp = ws::start(
             WebsocketActor {
                 hb: Instant::now(),
                 uuid,
-                backing_actor: addr,
-                last_send: "".to_owned(),
+                backing_actor: None,
+                views: ViewSync::default(),
             },
             &req,
             stream,
@@ -202,17 +391,73 @@ async fn websocket_connect(req: HttpRequest, stream: web::Payload) -> Result<Htt
 }
 
 /// Actor that holds the shared state
-#[derive(Default)]
 struct SharedLiveActor<S: SharedLiveState> {
     state: S,
     subs: HashMap<Addr<WebsocketActor>, UserUuid>,
+    timers: HashMap<game::TimerId, SpawnHandle>,
+    // Users whose last socket is gone, but who may still come back.
+    leaving: HashMap<UserUuid, SpawnHandle>,
+    persistence: Persistence,
+    log: Option<EventLog>, // Only missing if the log file could not be opened.
+    // Log entries covered by the snapshot this was restored from, if any.
+    restored: Option<u64>,
+    // Stopped once the live state needs no more ticks.
+    ticker: Option<SpawnHandle>,
+}
+
+/// How a live state gets to disk.
+struct Persistence {
+    store: SnapshotStore,
+    route: String,
+    // Unchanged snapshots are not written again.
+    last_written: Option<Snapshot>,
 }
 
 impl<S: SharedLiveState> SharedLiveActor<S> {
-    fn new(state: S) -> Self {
+    fn new(state: S, persistence: Persistence, log: Option<EventLog>) -> Self {
         Self {
             state,
             subs: HashMap::new(),
+            timers: HashMap::new(),
+            leaving: HashMap::new(),
+            persistence,
+            log,
+            restored: None,
+            ticker: None,
+        }
+    }
+
+    fn record(&mut self, entry: LogEntry) {
+        if let Some(log) = &mut self.log {
+            log.append(entry);
+        }
+    }
+
+    /// Write a snapshot of the live state, if it has changed. Running timers
+    /// are not part of the snapshot.
+    fn persist(&mut self) {
+        if let Some(log) = &mut self.log {
+            log.flush_ticks();
+        }
+        let persistence = &mut self.persistence;
+        let state = match serde_json::to_value(&self.state) {
+            Ok(state) => state,
+            Err(e) => {
+                error!("Could not encode {}: {}", persistence.route, e);
+                return;
+            }
+        };
+        let snapshot = Snapshot {
+            route: persistence.route.clone(),
+            state,
+            logged: self.log.as_ref().map_or(0, EventLog::entries),
+        };
+        if persistence.last_written.as_ref() == Some(&snapshot) {
+            return;
+        }
+        match persistence.store.save(&snapshot) {
+            Ok(()) => persistence.last_written = Some(snapshot),
+            Err(e) => error!("Could not persist {}: {}", persistence.route, e),
         }
     }
 }
@@ -222,82 +467,249 @@ impl<G: SharedLiveState> Actor for SharedLiveActor<G> {
 
     // Start game loop when actor starts
     fn started(&mut self, ctx: &mut Self::Context) {
+        // A snapshot that matches the end of the log continues it, otherwise
+        // the replay needs to know where the live state starts.
+        let log_continues = match (&self.log, self.restored) {
+            (Some(log), Some(logged)) => log.entries() == logged,
+            _ => false,
+        };
+        if !log_continues {
+            match serde_json::to_value(&self.state) {
+                Ok(state) => {
+                    let route = self.persistence.route.clone();
+                    self.record(LogEntry::Init { route, state });
+                }
+                Err(e) => error!("Could not encode {}: {}", self.persistence.route, e),
+            }
+        }
         if let Some(duration) = self.state.tick_frequency() {
-            ctx.run_interval(duration, |act, _ctx| {
-                act.state.process_tick(); // TODO: Figure out how we can handle effects here.
-                for sub in act.subs.keys() {
-                    sub.do_send(UpdateLiveState {
-                        data: act.state.user_view(&act.subs[sub]),
-                        route: G::route_id(),
-                    });
+            let ticker = ctx.run_interval(duration, |act, ctx| {
+                // Effects from a tick are handled just like effects from events,
+                // this also sends the new state to all subscribers.
+                if let Some(log) = &mut act.log {
+                    log.tick();
+                }
+                let effect = act.state.process_tick();
+                let fut = act.handle_live_effect(effect, ctx);
+                ctx.spawn(fut);
+                if act.state.tick_frequency().is_none() {
+                    if let Some(ticker) = act.ticker.take() {
+                        ctx.cancel_future(ticker);
+                    }
                 }
             });
+            self.ticker = Some(ticker);
+        }
+        ctx.run_interval(PERSIST_INTERVAL, |act, _ctx| act.persist());
+        if self.restored.is_some() {
+            for uuid in self.state.present_users() {
+                let fut = self.user_disconnected(uuid, ctx);
+                ctx.spawn(fut);
+            }
         }
     }
 }
 
 impl<G: SharedLiveState> SharedLiveActor<G> {
+    /// Applies the effect and then sends every subscriber their view once,
+    /// no matter how many effects were batched together.
     fn handle_live_effect(
         &mut self,
         effect: game::LiveEffect,
-    ) -> std::pin::Pin<Box<dyn Future<Output = ()>>> {
-        match effect {
-            game::LiveEffect::None => {
-                for sub in self.subs.iter() {
-                    sub.0.do_send(UpdateLiveState {
-                        data: self.state.user_view(sub.1),
-                        route: G::route_id(),
-                    });
-                }
+        ctx: &mut <Self as Actor>::Context,
+    ) -> ResponseActFuture<Self, ()> {
+        Box::pin(
+            self.apply_effect(effect, ctx)
+                .map(|(), act, _ctx| act.broadcast_views()),
+        )
+    }
 
-                Box::pin(async move {})
-            }
+    fn broadcast_views(&self) {
+        for sub in self.subs.iter() {
+            sub.0.do_send(UpdateLiveState {
+                data: self.state.user_view(sub.1),
+                route: G::route_id(),
+            });
+        }
+    }
+
+    fn apply_effect(
+        &mut self,
+        effect: game::LiveEffect,
+        ctx: &mut <Self as Actor>::Context,
+    ) -> ResponseActFuture<Self, ()> {
+        match effect {
+            game::LiveEffect::None => Box::pin(async move {}.into_actor(self)),
             game::LiveEffect::LiveRedirectInit(route, game_state) => {
                 // When processign a LiveRedirect with a game state, we need to
                 // ask the broker to ensure it exist and init it with the game state
                 // if it doesn't.
                 debug!("LiveRedirect to {} with setup", route);
                 let router = LiveRouteBroker::from_registry();
-                let m = RouteResolutionWithSetup(route, game_state);
-                let new_ref_req = router.send(m);
-
-                let all_subs: Vec<_> = self.subs.keys().cloned().collect();
-                self.subs.clear();
-
-                Box::pin(async move {
-                    let new_ref = new_ref_req
-                        .await
-                        .expect("Could not resolve route")
-                        .expect("Got 404 for route");
-
-                    // Redirect all subscribers to the new route
-                    for sub in all_subs {
-                        sub.do_send(PerformLiveRedirect(new_ref.clone()));
-                    }
-                })
+                let m = RouteResolutionWithSetup(route.clone(), game_state);
+                self.redirect_all(route, router.send(m))
             }
             game::LiveEffect::LiveRedirect(route) => {
                 debug!("LiveRedirect to {} without setup", route);
                 let router = LiveRouteBroker::from_registry();
-                let m = RouteResolution(route);
-                let new_ref_req = router.send(m);
+                let m = RouteResolution(route.clone());
+                self.redirect_all(route, router.send(m))
+            }
+            game::LiveEffect::Schedule(timer) => {
+                self.schedule(timer, ctx);
+                Box::pin(async move {}.into_actor(self))
+            }
+            game::LiveEffect::CancelTimer(id) => {
+                if let Some(handle) = self.timers.remove(&id) {
+                    ctx.cancel_future(handle);
+                }
+                Box::pin(async move {}.into_actor(self))
+            }
+            game::LiveEffect::Notify(audience, notification) => {
+                for (sub, uuid) in self.subs.iter() {
+                    if audience.contains(uuid) {
+                        sub.do_send(ShowNotification(notification.clone()));
+                    }
+                }
+                Box::pin(async move {}.into_actor(self))
+            }
+            game::LiveEffect::Batch(effects) => {
+                // Apply the effects one after another, so a redirect in the
+                // middle of a batch is done before the next effect runs.
+                let mut effects = effects.into_iter();
+                match effects.next() {
+                    None => Box::pin(async move {}.into_actor(self)),
+                    Some(first) => {
+                        let rest = game::LiveEffect::Batch(effects.collect());
+                        Box::pin(
+                            self.apply_effect(first, ctx)
+                                .then(move |(), act, ctx| act.apply_effect(rest, ctx)),
+                        )
+                    }
+                }
+            }
+        }
+    }
+
+    /// A socket of this user disconnected. The user may still be connected in
+    /// another browser tab, so the live state only learns about it when the
+    /// last socket is gone and the grace period is over.
+    ///
+    /// Note that this is not called when the live actor redirects everyone.
+    fn user_disconnected(
+        &mut self,
+        uuid: UserUuid,
+        ctx: &mut <Self as Actor>::Context,
+    ) -> ResponseActFuture<Self, ()> {
+        if self.subs.values().any(|other| other == &uuid) {
+            return Box::pin(async move {}.into_actor(self));
+        }
 
-                let all_subs: Vec<_> = self.subs.keys().cloned().collect();
-                self.subs.clear();
+        match self.state.leave_grace_period() {
+            None => {
+                self.record(LogEntry::Leave { user: uuid.clone() });
+                let effect = self.state.leave_user(uuid);
+                self.handle_live_effect(effect, ctx)
+            }
+            Some(grace_period) => {
+                let leaving_uuid = uuid.clone();
+                let handle = ctx.run_later(grace_period, move |act, ctx| {
+                    act.leaving.remove(&leaving_uuid);
+                    act.record(LogEntry::Leave {
+                        user: leaving_uuid.clone(),
+                    });
+                    let effect = act.state.leave_user(leaving_uuid);
+                    let fut = act.handle_live_effect(effect, ctx);
+                    ctx.spawn(fut);
+                });
+                if let Some(old_handle) = self.leaving.insert(uuid, handle) {
+                    ctx.cancel_future(old_handle);
+                }
+                Box::pin(async move {}.into_actor(self))
+            }
+        }
+    }
 
-                Box::pin(async move {
-                    let new_ref = new_ref_req
-                        .await
-                        .expect("Could not resolve route")
-                        .expect("Got 404 for route");
+    /// Starts the timer, replacing a running timer with the same id.
+    fn schedule(&mut self, timer: game::Timer, ctx: &mut <Self as Actor>::Context) {
+        let payload: G::Timer = match timer.payload.downcast::<G::Timer>() {
+            Ok(payload) => *payload,
+            Err(_) => {
+                error!(
+                    "Timer {:?} on route {} has the wrong payload type.",
+                    timer.id,
+                    G::route_id()
+                );
+                return;
+            }
+        };
+        if let Some(handle) = self.timers.remove(&timer.id) {
+            ctx.cancel_future(handle);
+        }
 
+        let id = timer.id.clone();
+        let handle = if timer.repeat {
+            ctx.run_interval(timer.delay, move |act, ctx| {
+                act.record_timer(&payload);
+                let effect = act.state.process_timer(payload.clone());
+                let fut = act.handle_live_effect(effect, ctx);
+                ctx.spawn(fut);
+            })
+        } else {
+            ctx.run_later(timer.delay, move |act, ctx| {
+                act.timers.remove(&id);
+                act.record_timer(&payload);
+                let effect = act.state.process_timer(payload);
+                let fut = act.handle_live_effect(effect, ctx);
+                ctx.spawn(fut);
+            })
+        };
+        self.timers.insert(timer.id, handle);
+    }
+
+    fn record_timer(&mut self, payload: &G::Timer) {
+        match serde_json::to_value(payload) {
+            Ok(payload) => self.record(LogEntry::Timer { payload }),
+            Err(e) => error!("Could not log timer on route {}: {}", G::route_id(), e),
+        }
+    }
+
+    /// Moves everyone who is subscribed right now over to the route once the
+    /// broker resolved it. If that fails they stay here and are told why.
+    /// Sockets that left while the route was resolved are not touched.
+    fn redirect_all<F>(&mut self, route: String, resolution: F) -> ResponseActFuture<Self, ()>
+    where
+        F: Future<Output = Result<RouteResult, MailboxError>> + 'static,
+    {
+        let all_subs: Vec<_> = self.subs.keys().cloned().collect();
+
+        Box::pin(resolution.into_actor(self).map(move |result, act, _ctx| {
+            match result.unwrap_or(Err(RouteError::MailboxClosed)) {
+                Ok(new_ref) => {
                     // Redirect all subscribers to the new route
                     for sub in all_subs {
-                        sub.do_send(PerformLiveRedirect(new_ref.clone()));
+                        if act.subs.remove(&sub).is_some() {
+                            sub.do_send(PerformLiveRedirect {
+                                route: route.clone(),
+                                backing_actor: new_ref.clone(),
+                            });
+                        }
                     }
-                })
+                }
+                Err(error) => {
+                    error!("LiveRedirect to {} failed: {}", route, error);
+                    for sub in all_subs
+                        .into_iter()
+                        .filter(|sub| act.subs.contains_key(sub))
+                    {
+                        sub.do_send(ReportRouteError {
+                            route: route.clone(),
+                            error,
+                        });
+                    }
+                }
             }
-        }
+        }))
     }
 }
 
@@ -305,7 +717,14 @@ impl<G: SharedLiveState> SharedLiveActor<G> {
 enum SharedLiveActorMessage {
     Subscribe(Addr<WebsocketActor>, UserUuid),
     Unsubscribe(Addr<WebsocketActor>),
-    ClientSideEvent { event: String, sender: UserUuid },
+    ClientSideEvent {
+        id: Option<u64>,
+        event: String,
+        sender: UserUuid,
+        socket: Addr<WebsocketActor>,
+    },
+    /// Write the live state to disk now, e.g. because the server shuts down.
+    Persist,
 }
 
 impl Message for SharedLiveActorMessage {
@@ -313,42 +732,78 @@ impl Message for SharedLiveActorMessage {
 }
 
 impl<G: SharedLiveState> Handler<SharedLiveActorMessage> for SharedLiveActor<G> {
-    type Result = ResponseFuture<()>;
+    type Result = ResponseActFuture<Self, ()>;
 
-    fn handle(&mut self, msg: SharedLiveActorMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: SharedLiveActorMessage, ctx: &mut Self::Context) -> Self::Result {
         match msg {
             SharedLiveActorMessage::Subscribe(sub, uuid) => {
                 println!("New connection from {}", uuid);
+                // They came back in time, so they never left.
+                if let Some(handle) = self.leaving.remove(&uuid) {
+                    ctx.cancel_future(handle);
+                }
                 self.subs.insert(sub, uuid.clone());
+                self.record(LogEntry::Join { user: uuid.clone() });
                 let effect = self.state.join_user(uuid);
                 println!("Connected sockets: {}", self.subs.len());
-                self.handle_live_effect(effect)
+                self.handle_live_effect(effect, ctx)
             }
             SharedLiveActorMessage::Unsubscribe(sub) => {
-                // Note that this does not tell the game implementation about the change.
-                // This is because here we just take care of the disconnecting websocket
-                // and the person who left may still be connected in another browser tab.
-                self.subs.remove(&sub);
+                let uuid = self.subs.remove(&sub);
                 println!("Remaining sockets: {}", self.subs.len());
-                Box::pin(async move {})
+                match uuid {
+                    Some(uuid) => self.user_disconnected(uuid, ctx),
+                    None => Box::pin(async move {}.into_actor(self)),
+                }
             }
-            SharedLiveActorMessage::ClientSideEvent { event, sender } => {
-                let event = match RemoteEvent::deserialize(&event) {
-                    Ok(event) => event,
-                    Err(_) => {
-                        println!(
+            SharedLiveActorMessage::Persist => {
+                self.persist();
+                Box::pin(async move {}.into_actor(self))
+            }
+            SharedLiveActorMessage::ClientSideEvent {
+                id,
+                event,
+                sender,
+                socket,
+            } => {
+                let parsed = match RemoteEvent::deserialize(&event) {
+                    Ok(parsed) => parsed,
+                    Err(e) => {
+                        debug!(
                             "Could not decode message as RemoteEvent: {} from sender {} on route {}",
                             event,
                             sender,
                             G::route_id()
                         );
-                        return Box::pin(async move {});
+                        socket.do_send(EventReply::<G::Rejection> {
+                            id,
+                            result: EventResult::Malformed(e.to_string()),
+                        });
+                        return Box::pin(async move {}.into_actor(self));
                     }
                 };
 
-                let effect = self.state.process_remote_event(event, sender);
-
-                self.handle_live_effect(effect)
+                match self.state.process_remote_event(parsed, sender.clone()) {
+                    Ok(effect) => {
+                        // The event is valid json, it was decoded just now.
+                        let event = serde_json::from_str(&event).unwrap_or_default();
+                        self.record(LogEntry::Event { sender, event });
+                        if id.is_some() {
+                            socket.do_send(EventReply::<G::Rejection> {
+                                id,
+                                result: EventResult::Ack,
+                            });
+                        }
+                        self.handle_live_effect(effect, ctx)
+                    }
+                    Err(rejection) => {
+                        socket.do_send(EventReply {
+                            id,
+                            result: EventResult::Rejected(rejection),
+                        });
+                        Box::pin(async move {}.into_actor(self))
+                    }
+                }
             }
         }
     }
@@ -357,11 +812,182 @@ impl<G: SharedLiveState> Handler<SharedLiveActorMessage> for SharedLiveActor<G>
 // new ones.                                                                  //
 ////////////////////////////////////////////////////////////////////////////////
 
-#[derive(Default)]
+/// Reasons why the broker could not hand out a live actor for a route.
+#[derive(Debug, Clone, Copy, Serialize)]
+enum RouteError {
+    /// No registered route matches, or the live state can't be created from
+    /// the route alone and was not set up yet.
+    NotFound,
+    /// The setup data of a `LiveRedirectInit` is not the live state of the route.
+    SetupTypeMismatch,
+    /// A `LiveRedirectInit` targets a route that is already running.
+    AlreadyInitialised,
+    /// The broker or a live actor could not be reached.
+    MailboxClosed,
+}
+
+impl Display for RouteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RouteError::NotFound => write!(f, "route not found"),
+            RouteError::SetupTypeMismatch => write!(f, "setup data has the wrong type"),
+            RouteError::AlreadyInitialised => write!(f, "route is already initialised"),
+            RouteError::MailboxClosed => write!(f, "mailbox closed"),
+        }
+    }
+}
+
+type RouteResult = Result<Recipient<SharedLiveActorMessage>, RouteError>;
+
+/// Type erased entry of the route table. There is one for each registered
+/// `SharedLiveState` and it holds all running instances of that state.
+trait LiveRoute {
+    fn pattern(&self) -> &'static str;
+
+    /// Find the actor for the captured path segments, spawning a new one if
+    /// the state can be created from the route alone.
+    fn resolve(&mut self, path: &str, segments: &[&str]) -> RouteResult;
+
+    /// Spawn a new actor for the captured path segments from the setup data.
+    fn resolve_with_setup(
+        &mut self,
+        path: &str,
+        segments: &[&str],
+        setup_data: Box<dyn Any + Send>,
+    ) -> RouteResult;
+
+    /// All running actors of this route.
+    fn instances(&self) -> Vec<Recipient<SharedLiveActorMessage>>;
+}
+
+struct RouteInstances<S: SharedLiveState> {
+    instances: HashMap<S::Params, Recipient<SharedLiveActorMessage>>,
+    store: SnapshotStore,
+}
+
+impl<S: SharedLiveState> RouteInstances<S> {
+    /// Restored live states let everyone who was present leave again, unless
+    /// they come back within the grace period. `restored` is the number of log
+    /// entries their snapshot covers.
+    fn spawn(
+        &mut self,
+        path: &str,
+        params: S::Params,
+        state: S,
+        restored: Option<u64>,
+    ) -> RouteResult {
+        let persistence = Persistence {
+            store: self.store.clone(),
+            route: path.to_owned(),
+            last_written: None,
+        };
+        let log = match EventLog::open(path) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                error!("Could not open event log for {}: {}", path, e);
+                None
+            }
+        };
+        let mut actor = SharedLiveActor::new(state, persistence, log);
+        actor.restored = restored;
+        let addr = actor.start().recipient();
+        self.instances.insert(params, addr.clone());
+        Ok(addr)
+    }
+}
+
+impl<S: SharedLiveState> LiveRoute for RouteInstances<S> {
+    fn pattern(&self) -> &'static str {
+        S::route_pattern()
+    }
+
+    fn resolve(&mut self, path: &str, segments: &[&str]) -> RouteResult {
+        let params = S::Params::from_segments(segments).ok_or(RouteError::NotFound)?;
+        if let Some(addr) = self.instances.get(&params) {
+            return Ok(addr.clone());
+        }
+        let state = S::from_route(&params).ok_or(RouteError::NotFound)?;
+        info!("Spawning new {} actor", S::route_id());
+        self.spawn(path, params, state, None)
+    }
+
+    fn resolve_with_setup(
+        &mut self,
+        path: &str,
+        segments: &[&str],
+        setup_data: Box<dyn Any + Send>,
+    ) -> RouteResult {
+        let params = S::Params::from_segments(segments).ok_or(RouteError::NotFound)?;
+        if self.instances.contains_key(&params) {
+            return Err(RouteError::AlreadyInitialised);
+        }
+        let state: S = *setup_data
+            .downcast::<S>()
+            .map_err(|_| RouteError::SetupTypeMismatch)?;
+        info!("Spawning new {} actor with setup data", S::route_id());
+        self.spawn(path, params, state, None)
+    }
+
+    fn instances(&self) -> Vec<Recipient<SharedLiveActorMessage>> {
+        self.instances.values().cloned().collect()
+    }
+}
+
 struct LiveRouteBroker {
-    setup: Option<Recipient<SharedLiveActorMessage>>,
-    pomp: Option<Recipient<SharedLiveActorMessage>>,
-    wedding: Option<Recipient<SharedLiveActorMessage>>,
+    routes: Vec<Box<dyn LiveRoute>>,
+    store: SnapshotStore,
+}
+
+impl RouteTable for LiveRouteBroker {
+    /// Make a `SharedLiveState` available under its route pattern. The live
+    /// states are written to disk regularly and on shutdown. Instances that
+    /// were saved before are restored right away.
+    fn register<S: SharedLiveState>(&mut self) {
+        debug!("Registering route {}", S::route_pattern());
+        let mut route = RouteInstances::<S> {
+            instances: HashMap::new(),
+            store: self.store.clone(),
+        };
+        for snapshot in self.store.load_all() {
+            let params = match game::match_route(S::route_pattern(), &snapshot.route) {
+                Some(segments) => S::Params::from_segments(&segments),
+                None => continue,
+            };
+            let params = match params {
+                Some(params) => params,
+                None => continue,
+            };
+            match serde_json::from_value::<S>(snapshot.state) {
+                Ok(state) => {
+                    info!("Restoring {} from disk", snapshot.route);
+                    let _ = route.spawn(&snapshot.route, params, state, Some(snapshot.logged));
+                }
+                Err(e) => warn!("Could not restore {}: {}", snapshot.route, e),
+            }
+        }
+        self.routes.push(Box::new(route));
+    }
+}
+
+impl LiveRouteBroker {
+    /// Find the route entry responsible for a path and the captured segments.
+    fn find_route<'a>(&mut self, path: &'a str) -> Option<(&mut Box<dyn LiveRoute>, Vec<&'a str>)> {
+        self.routes.iter_mut().find_map(|route| {
+            let segments = game::match_route(route.pattern(), path)?;
+            Some((route, segments))
+        })
+    }
+}
+
+impl Default for LiveRouteBroker {
+    fn default() -> Self {
+        let mut broker = LiveRouteBroker {
+            routes: Vec::new(),
+            store: SnapshotStore::from_env(),
+        };
+        routes::register_all(&mut broker);
+        broker
+    }
 }
 
 impl Supervised for LiveRouteBroker {}
@@ -377,89 +1003,60 @@ impl Actor for LiveRouteBroker {
 struct RouteResolution(String);
 
 impl Message for RouteResolution {
-    type Result = Option<Recipient<SharedLiveActorMessage>>;
-}
-
-lazy_static! {
-    static ref POMP_ROUTE: Regex = Regex::new(r"^/pomp/(\d+)$").unwrap();
-    static ref SETUP_ROUTE: Regex = Regex::new(r"^/pomp/(\d+)/setup$").unwrap();
+    type Result = RouteResult;
 }
 
 impl Handler<RouteResolution> for LiveRouteBroker {
-    type Result = Option<Recipient<SharedLiveActorMessage>>;
+    type Result = RouteResult;
 
     fn handle(&mut self, msg: RouteResolution, _ctx: &mut Self::Context) -> Self::Result {
-        if msg.0 == "/wedding" {
-            if self.wedding.is_none() {
-                let actor: SharedLiveActor<wedding::WeddingData> =
-                    SharedLiveActor::new(wedding::WeddingData::default());
-                let addr = actor.start();
-                self.wedding = Some(addr.recipient());
-            }
-            return Some(self.wedding.clone().unwrap());
-        }
-
         debug!("Resolving route {}", msg.0);
-
-        // TODO: This needs to be replaced by some propper router eventually.
-
-        // Resolve "/" to the index live actor.
-        // todo. For now we just send you to /setup/1.
-
-        // Resolve "/pomp/{game_id}" to the pomp live actor.
-        if POMP_ROUTE.is_match(&msg.0) {
-            // For now there is only a single game that can be played.
-            // This should not be set up automatically, because it needs to be
-            // set up via the /pomp/{game_id}/setup route.
-            return self.pomp.clone();
-        }
-
-        // Resolve "/pomp/{game_id}/setup" to the setup live actor.
-        if SETUP_ROUTE.is_match(&msg.0) {
-            // for now there is only a single game that can be played.
-            if self.setup.is_none() {
-                info!("Spawning new setup actor");
-                let actor: SharedLiveActor<setup::GameState> = SharedLiveActor::default();
-                let addr = actor.start();
-                self.setup = Some(addr.recipient());
-            }
-            return Some(self.setup.clone().unwrap());
-        }
-
-        None
+        let (route, segments) = self.find_route(&msg.0).ok_or(RouteError::NotFound)?;
+        route.resolve(&msg.0, &segments)
     }
 }
 
+/// Like `RouteResolution`, but the live actor is created from the setup data.
+/// This fails if the route is already running.
 struct RouteResolutionWithSetup(String, Box<dyn Any + Send>);
 
 impl Message for RouteResolutionWithSetup {
-    // TODO: Right now None is "not found" and "illegal setup". I should introduce
-    // a custom error type here. (With an Into<Error> impl for actix.)
-    type Result = Option<Recipient<SharedLiveActorMessage>>;
+    type Result = RouteResult;
 }
 
 impl Handler<RouteResolutionWithSetup> for LiveRouteBroker {
-    type Result = Option<Recipient<SharedLiveActorMessage>>;
+    type Result = RouteResult;
 
     fn handle(&mut self, msg: RouteResolutionWithSetup, _ctx: &mut Self::Context) -> Self::Result {
         debug!("Resolving route {} (with setup data)", msg.0);
+        let (route, segments) = self.find_route(&msg.0).ok_or(RouteError::NotFound)?;
+        route.resolve_with_setup(&msg.0, &segments, msg.1)
+    }
+}
 
-        if POMP_ROUTE.is_match(&msg.0) {
-            if self.pomp.is_none() {
-                info!("Spawning new pomp actor");
-                let game: pomp::GameState = *msg
-                    .1
-                    .downcast::<pomp::GameState>()
-                    .expect("Setup data is not a pomp::GameState");
-
-                let actor = SharedLiveActor::new(game);
-                let addr = actor.start();
-                self.pomp = Some(addr.recipient());
-            }
-            return self.pomp.clone();
-        }
+/// Write all live states to disk. Resolves once they are written.
+struct PersistAll;
+
+impl Message for PersistAll {
+    type Result = ();
+}
+
+impl Handler<PersistAll> for LiveRouteBroker {
+    type Result = ResponseFuture<()>;
 
-        todo!()
+    fn handle(&mut self, _msg: PersistAll, _ctx: &mut Self::Context) -> Self::Result {
+        let instances: Vec<_> = self.routes.iter().flat_map(|r| r.instances()).collect();
+        Box::pin(async move {
+            for instance in instances {
+                if instance
+                    .send(SharedLiveActorMessage::Persist)
+                    .await
+                    .is_err()
+                {
+                    error!("Could not reach a live actor to persist it.");
+                }
+            }
+        })
     }
 }
 
@@ -490,12 +1087,176 @@ fn init_logger() {
 // Actually starting the server //
 //////////////////////////////////
 
+/// Replays and simulations deal from the same decks as the server, so a log
+/// replays the cards it was played with. Broken decks should stop the server
+/// now and not when a game starts.
+fn install_decks() -> std::io::Result<()> {
+    let config = decks::DeckConfig::from_env().map_err(|e| std::io::Error::other(e.to_string()))?;
+    decks::install(config);
+    Ok(())
+}
+
+/// `pomp replay <log file>` prints the state an event log ends in as json,
+/// instead of starting the server.
+fn replay_command(path: &str) -> std::io::Result<()> {
+    let records = event_log::read_log(std::path::Path::new(path));
+    match records.and_then(|records| routes::replay(&records)) {
+        Ok(replay) => {
+            for entry in replay.divergences {
+                eprintln!("Entry {} does not match the replay.", entry + 1);
+            }
+            println!("{}", serde_json::to_string_pretty(&replay.state)?);
+            Ok(())
+        }
+        Err(e) => Err(std::io::Error::other(e.to_string())),
+    }
+}
+
+/// Exits with status 2 like other command line tools, so scripts notice
+/// arguments they got wrong.
+fn usage() -> ! {
+    eprintln!("Usage: pomp [replay <log file> | simulate <games> [players]]");
+    std::process::exit(2)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let simulation = match args[..] {
+        [] => None,
+        ["replay", path] => return install_decks().and_then(|()| replay_command(path)),
+        ["simulate", games] => games.parse().ok().map(|games| (games, 2)),
+        ["simulate", games, players] => {
+            let players = players.parse().ok().filter(|players| *players >= 2);
+            games.parse().ok().zip(players)
+        }
+        _ => usage(),
+    };
+    if args.first() == Some(&"simulate") && simulation.is_none() {
+        usage();
+    }
+
+    install_decks()?;
+    if let Some((games, players)) = simulation {
+        return simulate::simulate_command(games, players);
+    }
+
     init_logger();
 
+    // Start the broker now, so persisted live states are restored right away
+    // and not only when the first client connects.
+    let broker = LiveRouteBroker::from_registry();
+
     HttpServer::new(|| App::new().route("/ws", web::get().to(websocket_connect)))
         .bind("0.0.0.0:8080")?
         .run()
-        .await
+        .await?;
+
+    info!("Server stopped, persisting live states.");
+    if broker.send(PersistAll).await.is_err() {
+        error!("Could not persist live states on shutdown.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// How often the timer of `Repeater` fired, actors own their state.
+    static FIRED: AtomicU32 = AtomicU32::new(0);
+
+    /// Starts a repeating timer on the first tick and cancels it from the
+    /// third time it fires.
+    #[derive(Serialize, Deserialize)]
+    struct Repeater {
+        started: bool,
+    }
+
+    #[derive(Serialize)]
+    struct RepeaterView;
+
+    impl UserView for RepeaterView {}
+
+    impl RemoteEvent for () {
+        fn deserialize(s: &str) -> Result<Self, serde_json::Error> {
+            serde_json::from_str(s)
+        }
+    }
+
+    impl SharedLiveState for Repeater {
+        type Event = ();
+        type View = RepeaterView;
+        type Params = game::GameId;
+        type Timer = u32;
+        type Rejection = ();
+
+        fn route_pattern() -> &'static str {
+            "/repeater/{id}"
+        }
+
+        fn from_route(_params: &game::GameId) -> Option<Self> {
+            None
+        }
+
+        fn user_view(&self, _player: &UserUuid) -> RepeaterView {
+            RepeaterView
+        }
+
+        fn process_remote_event(&mut self, _: (), _: UserUuid) -> Result<game::LiveEffect, ()> {
+            Err(())
+        }
+
+        fn tick_frequency(&self) -> Option<Duration> {
+            if self.started {
+                return None;
+            }
+            Some(Duration::from_millis(10))
+        }
+
+        fn process_tick(&mut self) -> game::LiveEffect {
+            self.started = true;
+            game::LiveEffect::Schedule(game::Timer {
+                id: game::TimerId("repeat".to_owned()),
+                delay: Duration::from_millis(20),
+                repeat: true,
+                payload: Box::new(7u32),
+            })
+        }
+
+        fn process_timer(&mut self, payload: u32) -> game::LiveEffect {
+            assert_eq!(7, payload);
+            if FIRED.fetch_add(1, Ordering::SeqCst) + 1 == 3 {
+                return game::LiveEffect::CancelTimer(game::TimerId("repeat".to_owned()));
+            }
+            game::LiveEffect::None
+        }
+
+        fn join_user(&mut self, _player: UserUuid) -> game::LiveEffect {
+            game::LiveEffect::None
+        }
+
+        fn route_id() -> &'static str {
+            "repeater"
+        }
+    }
+
+    #[test]
+    fn test_repeating_timer_until_cancelled() {
+        System::new("test").block_on(async {
+            // The actor stops once nobody has its address anymore.
+            let persistence = Persistence {
+                store: SnapshotStore::new(std::env::temp_dir().join("pomp-repeater")),
+                route: "/repeater/1".to_owned(),
+                last_written: None,
+            };
+            let repeater = Repeater { started: false };
+            let _addr = SharedLiveActor::new(repeater, persistence, None).start();
+            // Time for far more than three intervals.
+            actix::clock::delay_for(Duration::from_millis(300)).await;
+        });
+        assert_eq!(3, FIRED.load(Ordering::SeqCst));
+    }
 }
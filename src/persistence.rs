@@ -0,0 +1,116 @@
+//! Snapshots of shared live states on disk, so restarting the server does not
+//! wipe running games. Every route instance is stored as one JSON file.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+/// Directory used when `POMP_STATE_DIR` is not set.
+const DEFAULT_STATE_DIR: &str = "state";
+
+/// What is written to disk for one route instance. The route is stored inside
+/// the file, so the file name is only there to help humans.
+#[derive(Serialize, Deserialize, PartialEq)]
+pub struct Snapshot {
+    pub route: String,
+    pub state: serde_json::Value,
+    /// Entries in the event log up to this state. When the log has more after
+    /// a restart, the restored state needs a new `Init` in the log.
+    #[serde(default)]
+    pub logged: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        SnapshotStore { dir: dir.into() }
+    }
+
+    /// Uses the directory in `POMP_STATE_DIR` or "./state".
+    pub fn from_env() -> Self {
+        match std::env::var_os("POMP_STATE_DIR") {
+            Some(dir) => SnapshotStore::new(dir),
+            None => SnapshotStore::new(DEFAULT_STATE_DIR),
+        }
+    }
+
+    fn path(&self, route: &str) -> PathBuf {
+        let name = route.trim_start_matches('/').replace('/', "_");
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Replaces the snapshot of the route. The file is written next to the old
+    /// one first, so a crash while writing never leaves half a snapshot behind.
+    pub fn save(&self, snapshot: &Snapshot) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&snapshot.route);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_vec(snapshot)?)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Reads all snapshots. Files that can't be read are skipped with a warning.
+    pub fn load_all(&self) -> Vec<Snapshot> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+            Err(e) => {
+                warn!("Could not read state directory {:?}: {}", self.dir, e);
+                return Vec::new();
+            }
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            match read_snapshot(&path) {
+                Ok(snapshot) => snapshots.push(snapshot),
+                Err(e) => warn!("Skipping snapshot {:?}: {}", path, e),
+            }
+        }
+        snapshots
+    }
+}
+
+fn read_snapshot(path: &Path) -> io::Result<Snapshot> {
+    let bytes = fs::read(path)?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("pomp-state-{}", std::process::id()));
+        let store = SnapshotStore::new(&dir);
+        assert!(store.load_all().is_empty());
+
+        let snapshot = Snapshot {
+            route: "/pomp/3".to_owned(),
+            state: serde_json::json!({"winner": null}),
+            logged: 12,
+        };
+        store.save(&snapshot).unwrap();
+        // Saving again replaces the old snapshot.
+        store.save(&snapshot).unwrap();
+
+        let loaded = store.load_all();
+        assert_eq!(1, loaded.len());
+        assert_eq!("/pomp/3", loaded[0].route);
+        assert_eq!(snapshot.state, loaded[0].state);
+        assert_eq!(12, loaded[0].logged);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
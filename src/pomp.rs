@@ -1,12 +1,22 @@
 //! Contains only core game logic for the Pomp game.
 //!
-use std::{collections::HashMap, mem, time::Duration};
+use std::{cmp::Reverse, collections::HashMap, mem, time::Duration};
 
-use rand::{distributions::Standard, prelude::Distribution, Rng};
+use rand::{
+    distributions::{Standard, WeightedIndex},
+    prelude::{Distribution, SliceRandom},
+    rngs::StdRng,
+    Rng, SeedableRng,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    game::{LiveEffect, RemoteEvent, SharedLiveState, UserUuid, UserView},
+    bots::BotKind,
+    decks::{self, DeckConfig, RandomCards, RandomTier},
+    game::{
+        Audience, GameId, LiveEffect, Notification, NotificationLevel, RemoteEvent,
+        SharedLiveState, UserUuid, UserView,
+    },
     setup,
 };
 
@@ -18,38 +28,132 @@ impl ElmExport for ElementVector {}
 impl ElmExport for Card {}
 impl ElmExport for ElementColor {}
 impl ElmExport for PompEvent {}
+impl ElmExport for PompRejection {}
+impl ElmExport for GambleView {}
+impl ElmExport for Power {}
+impl ElmExport for PowerEffect {}
+impl ElmExport for Patron {}
+impl ElmExport for Rank {}
+
+/// Each `PowerEffect::FasterEnergy` takes this part of the ticks per energy
+/// of the rules off.
+const FASTER_ENERGY_DIVISOR: u32 = 5;
+
+/// How many seconds it takes until players get the elements of their income.
+const SECONDS_PER_INCOME: u32 = 6;
+
+/// How many cards a player can reserve at the same time.
+const RESERVE_LIMIT: usize = 3;
+
+/// Chaos elements a player gets for reserving a card.
+const CHAOS_FOR_RESERVING: u32 = 1;
+
+/// Victory points a patron is worth.
+const PATRON_POINTS: u32 = 3;
+
+/// Energy needed to gamble, no matter how large the bundle is.
+const GAMBLE_PRICE: u32 = 3;
+
+/// Elements in the bundle right after someone gambled. Even a fresh bundle
+/// has more elements than `GAMBLE_PRICE` buys one by one.
+const GAMBLE_START_SIZE: u32 = 4;
+
+/// The bundle stops growing at this size.
+const GAMBLE_MAX_SIZE: u32 = 8;
+
+/// How many seconds it takes until the bundle grows by one element.
+const SECONDS_PER_GAMBLE_GROWTH: u32 = 4;
 
-/// Time resolution of the game.
-const TICKS_PER_SECOND: u64 = 5;
+/// How many seconds bots wait between two actions.
+const SECONDS_PER_BOT_ACTION: u32 = 1;
 
-/// How many ticks the game progresses before a player gets energy.
-const TICKS_PER_ENERGY: u8 = 2 * TICKS_PER_SECOND as u8;
+/// How long a player can be disconnected before they are shown as away.
+const LEAVE_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
-/// Score needed to win the game.
-const SCORE_TO_WIN: u32 = 15;
+/// Rules the host can change in the setup lobby before the game starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PompRules {
+    pub score_to_win: u32,           // Reaching this starts the end of the game.
+    pub grace_seconds: u32,          // Time the others get to catch up then.
+    pub ticks_per_second: u32,       // Time resolution of the game.
+    pub ticks_per_energy: u32,       // How many ticks before a player gets energy.
+    pub market_slots: Option<usize>, // Overrides the slots of every market row.
+    pub deck_percent: u32,           // Scales the random cards per player of the decks.
+    pub refill_decks: bool,          // Empty decks get new random cards.
+}
+
+impl Default for PompRules {
+    fn default() -> Self {
+        PompRules {
+            score_to_win: 15,
+            grace_seconds: 10,
+            ticks_per_second: 5,
+            ticks_per_energy: 10,
+            market_slots: None,
+            deck_percent: 100,
+            refill_decks: false,
+        }
+    }
+}
+
+impl PompRules {
+    /// Keeps the game playable and the server from ticking too fast.
+    pub fn is_valid(&self) -> bool {
+        (1..=100).contains(&self.score_to_win)
+            && self.grace_seconds <= 120
+            && (1..=20).contains(&self.ticks_per_second)
+            && (1..=600).contains(&self.ticks_per_energy)
+            && self.market_slots.is_none_or(|n| (1..=10).contains(&n))
+            && (10..=400).contains(&self.deck_percent)
+    }
+
+    fn ticks(&self, seconds: u32) -> u32 {
+        seconds * self.ticks_per_second
+    }
+}
 
 /// Shared state for one player
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct PompPlayerView {
-    my_inventory: PlayerInventoryView,
+    pub my_inventory: Option<PlayerInventoryView>, // None means you are spectating.
+    pub my_reserved: Vec<Card>,
     others: Vec<PlayerInventoryView>,
-    market: Vec<Option<Card>>,
-    winner: Option<String>,
+    pub market: Vec<Vec<Option<Card>>>, // One row per tier.
+    deck_sizes: Vec<usize>,             // Cards left to refill the market, per tier.
+    patrons: Vec<Patron>,
+    gamble: GambleView,
+    seconds_left: Option<u32>, // Someone reached the score, the game ends soon.
+    ranking: Vec<Rank>,        // Empty until the game is over.
+    seed: u32,                 // Start a new game with this seed to get the same cards.
 }
 
+/// What a player gets for gambling. The elements of the bundle are a surprise.
 #[derive(Debug, Default, Clone, Serialize)]
-struct PlayerInventoryView {
+struct GambleView {
+    size: u32,
+    price: u32,
+}
+
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PlayerInventoryView {
     name: String,
+    away: bool,
     points: u32,
-    energy: u32,
-    elements: ElementVector,
-    discount: ElementVector,
+    pub energy: u32,
+    energy_per_minute: u32,
+    pub elements: ElementVector,
+    pub discount: ElementVector,
+    income: ElementVector,      // Elements the player gets every few seconds.
+    reserved_tiers: Vec<usize>, // Others only see the tiers of reserved cards.
 }
 
 /// There are a lot of places where we need one number for each element.
 /// This is a helper struct to make it easier to do that.
-#[derive(Debug, Default, Clone, Serialize)]
-struct ElementVector {
+/// Elements that are left out when deserializing are 0.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ElementVector {
     fire: u32,
     plant: u32,
     water: u32,
@@ -69,8 +173,18 @@ impl ElementVector {
         }
     }
 
+    pub fn get(&self, element: ElementColor) -> u32 {
+        match element {
+            ElementColor::Fire => self.fire,
+            ElementColor::Plant => self.plant,
+            ElementColor::Water => self.water,
+            ElementColor::Earth => self.earth,
+            ElementColor::Chaos => self.chaos,
+        }
+    }
+
     /// Calculate the total value of all elements.
-    fn total(&self) -> u32 {
+    pub fn total(&self) -> u32 {
         self.fire + self.plant + self.water + self.earth + self.chaos
     }
 
@@ -97,7 +211,7 @@ impl ElementVector {
 
     /// Version of minus where it is assumed that we may go into negative numbers
     /// and those are converted to 0. Useful for discounts.
-    fn restricted_minus(minuend: &Self, subtrahend: &Self) -> Self {
+    pub fn restricted_minus(minuend: &Self, subtrahend: &Self) -> Self {
         Self {
             fire: minuend.fire.saturating_sub(subtrahend.fire),
             plant: minuend.plant.saturating_sub(subtrahend.plant),
@@ -107,26 +221,81 @@ impl ElementVector {
         }
     }
 
-    // Return the pointwise sum of the two vectors.
-    fn sum(vector1: &ElementVector, vector2: &ElementVector) -> ElementVector {
-        ElementVector {
-            fire: vector1.fire + vector2.fire,
-            plant: vector1.plant + vector2.plant,
-            water: vector1.water + vector2.water,
-            earth: vector1.earth + vector2.earth,
-            chaos: vector1.chaos + vector2.chaos,
+    /// Elements that must be paid from `elements` to buy something with this
+    /// cost, or `None` if it can't be afforded. The discount of a color only
+    /// counts for that color, while the chaos discount is a wildcard for any
+    /// color. Wildcards first cover what the elements can't, then chaos
+    /// elements do, which are used up. The rest of the wildcards saves as
+    /// many elements as possible. Costs never contain chaos.
+    pub fn payment(&self, elements: &Self, discount: &Self) -> Option<Self> {
+        debug_assert_eq!(self.chaos, 0);
+        let mut payment = ElementVector::restricted_minus(self, discount);
+        let mut wildcards = discount.chaos;
+        for color in ElementColor::BASIC {
+            let missing = payment.get(color).saturating_sub(elements.get(color));
+            let covered = missing.min(wildcards);
+            wildcards -= covered;
+            let from_chaos = missing - covered;
+            if payment.chaos + from_chaos > elements.chaos {
+                return None;
+            }
+            payment.remove_element_ip(color, missing);
+            payment.add_element_ip(ElementColor::Chaos, from_chaos);
+        }
+        for color in ElementColor::BASIC {
+            let saved = wildcards.min(payment.get(color));
+            wildcards -= saved;
+            payment.remove_element_ip(color, saved);
+        }
+        Some(payment)
+    }
+
+    /// Lists the elements for humans, like "2 Fire, 1 Water".
+    fn describe(&self) -> String {
+        let parts: Vec<String> = ElementColor::BASIC
+            .iter()
+            .filter(|color| self.get(**color) > 0)
+            .map(|color| format!("{} {:?}", self.get(*color), color))
+            .collect();
+        if parts.is_empty() {
+            "nothing".to_owned()
+        } else {
+            parts.join(", ")
+        }
+    }
+
+    /// Adds the other vector to this one.
+    fn add_ip(&mut self, other: &Self) {
+        self.fire += other.fire;
+        self.plant += other.plant;
+        self.water += other.water;
+        self.earth += other.earth;
+        self.chaos += other.chaos;
+    }
+
+    fn remove_element_ip(&mut self, element: ElementColor, value: u32) {
+        match element {
+            ElementColor::Fire => self.fire -= value,
+            ElementColor::Plant => self.plant -= value,
+            ElementColor::Water => self.water -= value,
+            ElementColor::Earth => self.earth -= value,
+            ElementColor::Chaos => self.chaos -= value,
         }
     }
 }
 
 impl PlayerInventoryView {
-    fn public_info(inv: &PlayerData) -> PlayerInventoryView {
+    fn public_info(inv: &PlayerData, rules: &PompRules) -> PlayerInventoryView {
         PlayerInventoryView {
             name: inv.name.clone(),
+            away: inv.away,
             points: inv.points,
             energy: inv.energy,
+            energy_per_minute: rules.ticks(60) / inv.ticks_per_energy,
             elements: inv.elements.clone(),
             discount: inv.discount.clone(),
+            income: inv.income.clone(),
+            reserved_tiers: inv.reserved.iter().map(|r| r.tier).collect(),
         }
     }
 }
@@ -134,73 +303,445 @@ impl PlayerInventoryView {
 impl UserView for PompPlayerView {}
 
 /// Total state of the whole game.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct GameState {
     players: HashMap<UserUuid, PlayerData>,
-    deck_1: Vec<Card>,
-    deck_2: Vec<Card>,
-    deck_3: Vec<Card>,
-    market: Vec<Option<Card>>,
-    winner: Option<String>,
+    decks: Vec<Vec<Card>>,          // One deck per tier, drawn from the back.
+    market: Vec<Vec<Option<Card>>>, // One row per tier.
+    next_card_id: usize,            // Refilled decks need new ids.
+    refills: u32,                   // Seeds the next refill together with the game seed.
+    random_cards: RandomCards,      // Refills deal from the decks the game started with.
+    patrons: Vec<Patron>,
+    gamble: Gamble,
+    // Power cards on the market get cheaper every `ticks_per_decay` ticks.
+    ticks_per_decay: u32,
+    decay_ticks: u32,
+    // Bots are players too, they act every few ticks in this order.
+    bots: Vec<BotSeat>,
+    bot_ticks: u32,
+    // Counts down once someone reached the score, the game ends at 0.
+    end_ticks: Option<u32>,
+    ranking: Vec<Rank>, // Empty while the game is running.
+    // All cards are generated from this seed, so a game can be reproduced.
+    seed: u32,
+    rules: PompRules,
+}
+
+/// A bot player and the strategy it plays with. This is a struct and not a
+/// tuple, because rust_elm_typegen can't handle tuples.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BotSeat {
+    uuid: UserUuid,
+    kind: BotKind,
+}
+
+/// A bundle of random elements for a fixed price that grows until someone
+/// buys it. Then it starts small again.
+#[derive(Debug, Serialize, Deserialize)]
+struct Gamble {
+    size: u32,
+    growth_ticks: u32,
+    bought: u32, // Seeds the next bundle together with the game seed.
+}
+
+impl Gamble {
+    fn new() -> Self {
+        Gamble {
+            size: GAMBLE_START_SIZE,
+            growth_ticks: 0,
+            bought: 0,
+        }
+    }
+
+    fn tick(&mut self, rules: &PompRules) {
+        if self.size >= GAMBLE_MAX_SIZE {
+            return;
+        }
+        self.growth_ticks += 1;
+        if self.growth_ticks >= rules.ticks(SECONDS_PER_GAMBLE_GROWTH) {
+            self.growth_ticks = 0;
+            self.size += 1;
+        }
+    }
+
+    /// Draws the bundle and starts over with a small one. The bundle only
+    /// depends on the game seed and how often someone gambled before, so
+    /// replaying a game gives the same bundles.
+    fn draw(&mut self, seed: u32) -> ElementVector {
+        let mut rng = StdRng::seed_from_u64(((seed as u64) << 32) | self.bought as u64);
+        let mut bundle = ElementVector::default();
+        for _ in 0..self.size {
+            bundle.add_element_ip(*ElementColor::BASIC.choose(&mut rng).unwrap(), 1);
+        }
+        *self = Gamble {
+            bought: self.bought + 1,
+            ..Gamble::new()
+        };
+        bundle
+    }
+}
+
+impl Default for Gamble {
+    fn default() -> Self {
+        Gamble::new()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 struct PlayerData {
     name: String,
-    enery_fraction_ticks: u8,
+    away: bool, // Not connected right now.
+    enery_fraction_ticks: u32,
+    ticks_per_energy: u32, // Power cards make this smaller.
     energy: u32,
     points: u32,
     elements: ElementVector,
     discount: ElementVector,
+    income: ElementVector,
+    income_ticks: u32,
+    reserved: Vec<ReservedCard>,
+    #[serde(default)]
+    bought: Vec<BoughtCard>,
+}
+
+/// What is left of a card after a player bought it, used for statistics.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoughtCard {
+    pub tier: usize,
+    pub color: ElementColor,
+    pub points: u32,
+}
+
+/// Bonus points for the first player whose discount meets the requirement.
+/// Only the discount of basic colors counts, wildcards don't.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Patron {
+    id: usize,
+    requirement: ElementVector,
+    points: u32,
+    owner: Option<String>, // Name of the player who got the patron.
+}
+
+impl Patron {
+    /// Wants either 4 discount in two colors or 3 discount in three colors.
+    fn random<R: Rng>(id: usize, rng: &mut R) -> Self {
+        let colors = rng.gen_range(2..=3);
+        let amount = if colors == 2 { 4 } else { 3 };
+        let mut requirement = ElementVector::default();
+        for color in ElementColor::BASIC.choose_multiple(rng, colors) {
+            requirement.add_element_ip(*color, amount);
+        }
+        Patron {
+            id,
+            requirement,
+            points: PATRON_POINTS,
+            owner: None,
+        }
+    }
+}
+
+/// Gives the player the first free patron they meet the requirement of, at
+/// most one per card they buy.
+fn award_patron(patrons: &mut [Patron], inventory: &mut PlayerData) -> LiveEffect {
+    let discount = ElementVector {
+        chaos: 0,
+        ..inventory.discount.clone()
+    };
+    let patron = patrons
+        .iter_mut()
+        .find(|p| p.owner.is_none() && discount.geq(&p.requirement));
+    match patron {
+        Some(patron) => {
+            patron.owner = Some(inventory.name.clone());
+            inventory.points += patron.points;
+            let text = format!("{} got a patron.", inventory.name);
+            LiveEffect::Notify(
+                Audience::Everyone,
+                Notification::new(NotificationLevel::Info, text),
+            )
+        }
+        None => LiveEffect::None,
+    }
+}
+
+/// One line of the final ranking. Players are ranked by points, then by who
+/// bought fewer cards, then by who has more elements left. Players that tie
+/// in all of these share their place.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rank {
+    place: usize, // Counted from 1.
+    pub name: String,
+    points: u32,
+    cards: usize,
+    elements: u32,
+}
+
+impl Rank {
+    fn sort_key(&self) -> (Reverse<u32>, usize, Reverse<u32>) {
+        (Reverse(self.points), self.cards, Reverse(self.elements))
+    }
+}
+
+/// A card taken from the market that only its owner can buy.
+#[derive(Debug, Serialize, Deserialize)]
+struct ReservedCard {
+    tier: usize,
+    card: Card,
 }
 
 impl PlayerData {
-    fn new(name: String) -> Self {
+    fn new(name: String, rules: &PompRules) -> Self {
         Self {
             name,
+            away: false,
             enery_fraction_ticks: 0,
+            ticks_per_energy: rules.ticks_per_energy,
             energy: 0,
             points: 0,
             elements: ElementVector::default(),
             discount: ElementVector::default(),
+            income: ElementVector::default(),
+            income_ticks: 0,
+            reserved: Vec::new(),
+            bought: Vec::new(),
+        }
+    }
+
+    /// Pays for the card and adds it to the inventory.
+    fn gain_card(&mut self, tier: usize, card: Card, payment: &ElementVector, rules: &PompRules) {
+        self.elements.minus_ip(payment);
+        self.discount.add_element_ip(card.color, 1);
+        self.points += card.points;
+        self.bought.push(BoughtCard {
+            tier,
+            color: card.color,
+            points: card.points,
+        });
+        if let Some(power) = card.power {
+            self.apply_power(power.effect, rules);
+        }
+    }
+
+    /// Energy and income of power cards trickle in over time.
+    fn tick(&mut self, rules: &PompRules) {
+        self.enery_fraction_ticks += 1;
+        if self.enery_fraction_ticks >= self.ticks_per_energy {
+            self.enery_fraction_ticks = 0;
+            self.energy += 1;
+        }
+        self.income_ticks += 1;
+        if self.income_ticks >= rules.ticks(SECONDS_PER_INCOME) {
+            self.income_ticks = 0;
+            self.elements.add_ip(&self.income);
+        }
+    }
+
+    /// Players never get more than one energy per second.
+    fn apply_power(&mut self, effect: PowerEffect, rules: &PompRules) {
+        match effect {
+            PowerEffect::FasterEnergy => {
+                // Energy never comes faster than once per second, but rules
+                // that are already faster than that don't get slowed down.
+                let faster = (rules.ticks_per_energy / FASTER_ENERGY_DIVISOR).max(1);
+                let fastest = rules.ticks(1).min(self.ticks_per_energy);
+                self.ticks_per_energy = self.ticks_per_energy.saturating_sub(faster).max(fastest);
+            }
+            PowerEffect::ElementIncome(color) => self.income.add_element_ip(color, 1),
         }
     }
 }
 
 impl GameState {
-    pub fn from_setup(setup_data: &setup::GameState) -> Self {
-        let mut inventories = HashMap::new();
-        for (uuid, setup_data) in &setup_data.data {
-            inventories.insert(uuid.clone(), PlayerData::new(setup_data.name.clone()));
+    pub fn rules(&self) -> &PompRules {
+        &self.rules
+    }
+
+    pub fn is_over(&self) -> bool {
+        !self.ranking.is_empty()
+    }
+
+    /// Best player first, empty while the game is running.
+    pub fn ranking(&self) -> &[Rank] {
+        &self.ranking
+    }
+
+    /// Ranks all players and ends the game.
+    fn finish(&mut self) -> LiveEffect {
+        let mut ranking: Vec<Rank> = self
+            .players
+            .values()
+            .map(|data| Rank {
+                place: 0,
+                name: data.name.clone(),
+                points: data.points,
+                cards: data.bought.len(),
+                elements: data.elements.total(),
+            })
+            .collect();
+        // Names only keep the order of shared places stable.
+        ranking.sort_by(|a, b| {
+            a.sort_key()
+                .cmp(&b.sort_key())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+        for i in 0..ranking.len() {
+            ranking[i].place = match i {
+                0 => 1,
+                _ if ranking[i].sort_key() == ranking[i - 1].sort_key() => ranking[i - 1].place,
+                _ => i + 1,
+            };
         }
-        let (mut deck_1, mut deck_2, mut deck_3) = Card::random_deck(inventories.len());
+        self.end_ticks = None;
+        self.ranking = ranking;
 
-        let mut market = Vec::with_capacity(15);
+        let winners: Vec<&str> = self
+            .ranking
+            .iter()
+            .filter(|rank| rank.place == 1)
+            .map(|rank| rank.name.as_str())
+            .collect();
+        let text = match winners[..] {
+            [winner] => format!("{} wins the game!", winner),
+            _ => format!("{} share the win!", winners.join(" and ")),
+        };
+        LiveEffect::Notify(
+            Audience::Everyone,
+            Notification::new(NotificationLevel::Success, text),
+        )
+    }
 
-        for _ in 0..5 {
-            market.push(deck_1.pop());
+    /// With `refill_decks`, an empty deck gets new random cards of its tier.
+    /// Then empty market slots are filled from the decks again.
+    fn refill_market(&mut self) {
+        let random_cards = &self.random_cards;
+        for (tier, deck) in self.decks.iter_mut().enumerate() {
+            let refill = self.rules.refill_decks && deck.is_empty();
+            if let Some(random_tier) = random_cards.tiers.get(tier).filter(|_| refill) {
+                // The high bit keeps these seeds apart from the gamble seeds.
+                let seed = ((self.seed as u64) << 32) | (1 << 31) | self.refills as u64;
+                let mut rng = StdRng::seed_from_u64(seed);
+                let cards = Card::random_tier_cards(
+                    random_tier,
+                    random_cards.chaos_surcharge,
+                    &self.rules,
+                    self.players.len(),
+                    &mut self.next_card_id,
+                    &mut rng,
+                );
+                // Tiers without random cards can't be refilled.
+                if !cards.is_empty() {
+                    *deck = cards;
+                    self.refills += 1;
+                }
+            }
+            for slot in self.market[tier].iter_mut().filter(|slot| slot.is_none()) {
+                *slot = deck.pop();
+            }
         }
-        for _ in 0..5 {
-            market.push(deck_2.pop());
+    }
+
+    /// Elements can always be bought with energy, so as long as there is a
+    /// card on the market or in someone's reserve, it can be bought
+    /// eventually. Without any cards the game is stuck.
+    fn is_stalemate(&self) -> bool {
+        self.market.iter().flatten().all(Option::is_none)
+            && self.players.values().all(|data| data.reserved.is_empty())
+    }
+
+    /// Cards bought by all players together, in no particular order.
+    pub fn bought_cards(&self) -> impl Iterator<Item = &BoughtCard> {
+        self.players.values().flat_map(|data| &data.bought)
+    }
+
+    /// Every bot sends the event its strategy picks, like a human would.
+    /// Rejected events are dropped, the bot will try something else later.
+    fn act_bots(&mut self) -> LiveEffect {
+        let mut effects = Vec::new();
+        for bot in self.bots.clone() {
+            if self.is_over() {
+                break;
+            }
+            let view = self.user_view(&bot.uuid);
+            if let Some(event) = bot.kind.strategy().act(&view) {
+                if let Ok(effect) = self.process_remote_event(event, bot.uuid) {
+                    effects.push(effect);
+                }
+            }
         }
-        for _ in 0..5 {
-            market.push(deck_3.pop());
+        LiveEffect::Batch(effects)
+    }
+
+    /// Deals the cards of the installed decks with a RNG seeded from `seed`.
+    /// The same seed, rules, decks and number of players give the same cards.
+    pub fn from_setup(setup_data: &setup::GameState, seed: u32, rules: PompRules) -> Self {
+        GameState::deal(setup_data, seed, rules, decks::current())
+    }
+
+    fn deal(
+        setup_data: &setup::GameState,
+        seed: u32,
+        rules: PompRules,
+        config: &DeckConfig,
+    ) -> Self {
+        let mut inventories = HashMap::new();
+        let mut bots = Vec::new();
+        for (uuid, setup_data) in &setup_data.data {
+            let inventory = PlayerData::new(setup_data.name.clone(), &rules);
+            inventories.insert(uuid.clone(), inventory);
+            if let Some(kind) = setup_data.bot {
+                bots.push(BotSeat {
+                    uuid: uuid.clone(),
+                    kind,
+                });
+            }
         }
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        let mut next_card_id = 0;
+        let mut decks = Card::random_decks(
+            config,
+            &rules,
+            inventories.len(),
+            &mut next_card_id,
+            &mut rng,
+        );
 
-        debug_assert_eq!(market.len(), 15);
+        let market = decks
+            .iter_mut()
+            .zip(&config.tiers)
+            .map(|(deck, tier)| {
+                let slots = rules.market_slots.unwrap_or(tier.market_slots);
+                (0..slots).map(|_| deck.pop()).collect()
+            })
+            .collect();
+        // Like in Splendor there is one patron more than there are players.
+        let patrons = (0..=inventories.len())
+            .map(|id| Patron::random(id, &mut rng))
+            .collect();
 
         GameState {
             players: inventories,
-            deck_1,
-            deck_2,
-            deck_3,
+            decks,
             market,
-            winner: None,
+            next_card_id,
+            refills: 0,
+            random_cards: config.random_cards(),
+            patrons,
+            gamble: Gamble::new(),
+            ticks_per_decay: rules.ticks(config.power_decay_seconds),
+            decay_ticks: 0,
+            bots,
+            bot_ticks: 0,
+            end_ticks: None,
+            ranking: Vec::new(),
+            seed,
+            rules,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Chaos is special: it can't be bought with energy and chaos cards give a
+/// wildcard discount instead of a discount on chaos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ElementColor {
     Fire,
     Plant,
@@ -209,11 +750,37 @@ pub enum ElementColor {
     Chaos,
 }
 
+impl ElementColor {
+    /// All colors that can be bought with energy and appear in costs.
+    pub const BASIC: [ElementColor; 4] = [
+        ElementColor::Fire,
+        ElementColor::Plant,
+        ElementColor::Water,
+        ElementColor::Earth,
+    ];
+}
+
 /// RemoteEvent custom type. This depents on the business logic we have.
 #[derive(Debug, Clone, Deserialize)]
 pub enum PompEvent {
     Buy(ElementColor),
     BuyCard(usize),
+    Reserve(usize),     // Take a card from the market, only you can buy it then.
+    BuyReserved(usize), // Buy a card you reserved.
+    Gamble,
+}
+
+/// Why a `PompEvent` was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum PompRejection {
+    GameOver,
+    NotAPlayer, // Spectators can't buy anything.
+    NotEnoughEnergy,
+    ChaosNotForSale, // Chaos only comes from chaos cards.
+    CardNotOnMarket, // Usually someone else was faster.
+    CardNotReserved,
+    ReserveFull,
+    NotEnoughElements,
 }
 
 impl RemoteEvent for PompEvent {
@@ -225,146 +792,300 @@ impl RemoteEvent for PompEvent {
 impl SharedLiveState for GameState {
     type View = PompPlayerView;
     type Event = PompEvent;
+    type Params = GameId;
+    type Timer = ();
+    type Rejection = PompRejection;
 
-    /// Extract information that is relevant for one player and hide the rest.
-    fn user_view(&self, player: &UserUuid) -> PompPlayerView {
-        // My inventory
-        let my_data = self
-            .players
-            .get(player)
-            .expect("Player inventory not found");
+    fn route_pattern() -> &'static str {
+        "/pomp/{id}"
+    }
 
-        let my_inventory = PlayerInventoryView::public_info(my_data);
+    /// A game of pomp can only be started from its setup route.
+    fn from_route(_game_id: &GameId) -> Option<Self> {
+        None
+    }
 
-        let mut others = Vec::with_capacity(self.players.len() - 1);
+    /// Extract information that is relevant for one player and hide the rest.
+    /// Spectators have no inventory and see everybody as one of the others.
+    fn user_view(&self, player: &UserUuid) -> PompPlayerView {
+        let my_data = self.players.get(player);
+        let my_inventory = my_data.map(|data| PlayerInventoryView::public_info(data, &self.rules));
+        let my_reserved = match my_data {
+            Some(data) => data.reserved.iter().map(|r| r.card.clone()).collect(),
+            None => Vec::new(),
+        };
 
-        for (uuid, data) in self.players.iter() {
-            if uuid != player {
-                others.push(PlayerInventoryView::public_info(data));
-            }
-        }
+        let others = self
+            .players
+            .iter()
+            .filter(|(uuid, _)| *uuid != player)
+            .map(|(_, data)| PlayerInventoryView::public_info(data, &self.rules))
+            .collect();
 
         PompPlayerView {
             my_inventory,
+            my_reserved,
             others,
             market: self.market.clone(),
-            winner: self.winner.clone(),
+            deck_sizes: self.decks.iter().map(Vec::len).collect(),
+            patrons: self.patrons.clone(),
+            gamble: GambleView {
+                size: self.gamble.size,
+                price: GAMBLE_PRICE,
+            },
+            seconds_left: self
+                .end_ticks
+                .map(|ticks| ticks.div_ceil(self.rules.ticks_per_second)),
+            ranking: self.ranking.clone(),
+            seed: self.seed,
         }
     }
 
     /// Process a remote event.
-    fn process_remote_event(&mut self, event: PompEvent, sender: UserUuid) -> LiveEffect {
-        if self.winner.is_some() {
-            // Game is over.
-            return LiveEffect::None;
+    fn process_remote_event(
+        &mut self,
+        event: PompEvent,
+        sender: UserUuid,
+    ) -> Result<LiveEffect, PompRejection> {
+        if self.is_over() {
+            return Err(PompRejection::GameOver);
         }
+        let inventory = self
+            .players
+            .get_mut(&sender)
+            .ok_or(PompRejection::NotAPlayer)?;
 
-        match event {
+        let effect = match event {
             PompEvent::Buy(color) => {
-                let inventory = self.players.get_mut(&sender).unwrap();
-                inventory.buy(color);
+                inventory.buy(color)?;
+                LiveEffect::None
             }
             PompEvent::BuyCard(id) => {
                 // First, check if this card is currently on the market.
                 // If it isn't there, this can be a timing issue where two players
                 // try to buy the same card at the same time.
-                let inventory = self.players.get_mut(&sender).unwrap();
-                let mut market_index = 999;
-                for (i, c) in self.market.iter().enumerate() {
-                    if let Some(c) = c {
-                        if c.id == id
-                            && ElementVector::sum(&inventory.elements, &inventory.discount)
-                                .geq(&c.cost)
-                        {
-                            market_index = i;
-                            break;
-                        }
-                    }
-                }
-                if market_index == 999 {
-                    // This card is not on the market or not affordable.
-                    return LiveEffect::None;
+                let (tier, slot) =
+                    find_on_market(&self.market, id).ok_or(PompRejection::CardNotOnMarket)?;
+                let cost = &self.market[tier][slot].as_ref().unwrap().cost;
+                let payment = cost
+                    .payment(&inventory.elements, &inventory.discount)
+                    .ok_or(PompRejection::NotEnoughElements)?;
+                let card = take_from_market(&mut self.market, &mut self.decks, tier, slot);
+                inventory.gain_card(tier, card, &payment, &self.rules);
+                award_patron(&mut self.patrons, inventory)
+            }
+            PompEvent::Reserve(id) => {
+                let (tier, slot) =
+                    find_on_market(&self.market, id).ok_or(PompRejection::CardNotOnMarket)?;
+                if inventory.reserved.len() >= RESERVE_LIMIT {
+                    return Err(PompRejection::ReserveFull);
                 }
-                let mut new_card = if market_index < 5 {
-                    self.deck_1.pop()
-                } else if market_index < 10 {
-                    self.deck_2.pop()
-                } else {
-                    self.deck_3.pop()
-                };
-                mem::swap(&mut self.market[market_index], &mut new_card);
-                let new_card = new_card.unwrap();
+                let card = take_from_market(&mut self.market, &mut self.decks, tier, slot);
+                inventory.reserved.push(ReservedCard { tier, card });
                 inventory
                     .elements
-                    .minus_ip(&ElementVector::restricted_minus(
-                        &new_card.cost,
-                        &inventory.discount,
-                    ));
-                inventory.discount.add_element_ip(new_card.color, 1);
-                inventory.points += new_card.points;
+                    .add_element_ip(ElementColor::Chaos, CHAOS_FOR_RESERVING);
+                LiveEffect::None
             }
-        }
-
-        // Check if we have a winner.
-        for (_, inventory) in self.players.iter() {
-            if inventory.points >= SCORE_TO_WIN {
-                self.winner = Some(inventory.name.clone());
-                break;
+            PompEvent::BuyReserved(id) => {
+                let index = inventory
+                    .reserved
+                    .iter()
+                    .position(|r| r.card.id == id)
+                    .ok_or(PompRejection::CardNotReserved)?;
+                let payment = inventory.reserved[index]
+                    .card
+                    .cost
+                    .payment(&inventory.elements, &inventory.discount)
+                    .ok_or(PompRejection::NotEnoughElements)?;
+                let reserved = inventory.reserved.remove(index);
+                inventory.gain_card(reserved.tier, reserved.card, &payment, &self.rules);
+                award_patron(&mut self.patrons, inventory)
             }
-        }
+            PompEvent::Gamble => {
+                if inventory.energy < GAMBLE_PRICE {
+                    return Err(PompRejection::NotEnoughEnergy);
+                }
+                inventory.energy -= GAMBLE_PRICE;
+                let bundle = self.gamble.draw(self.seed);
+                inventory.elements.add_ip(&bundle);
+                let text = format!("You won {}.", bundle.describe());
+                LiveEffect::Notify(
+                    Audience::User(sender.clone()),
+                    Notification::new(NotificationLevel::Info, text),
+                )
+            }
+        };
 
-        LiveEffect::None
+        // Only the sender can get points, so only they can reach the score now.
+        // The others still get the grace period to catch up.
+        let inventory = &self.players[&sender];
+        if self.end_ticks.is_some() || inventory.points < self.rules.score_to_win {
+            return Ok(effect);
+        }
+        if self.rules.grace_seconds == 0 {
+            return Ok(LiveEffect::Batch(vec![effect, self.finish()]));
+        }
+        let text = format!(
+            "{} reached {} points, the game ends in {} seconds.",
+            inventory.name, self.rules.score_to_win, self.rules.grace_seconds
+        );
+        self.end_ticks = Some(self.rules.ticks(self.rules.grace_seconds));
+        Ok(LiveEffect::Batch(vec![
+            effect,
+            LiveEffect::Notify(
+                Audience::Everyone,
+                Notification::new(NotificationLevel::Info, text),
+            ),
+        ]))
     }
 
     fn tick_frequency(&self) -> Option<Duration> {
-        // Game Loop runs at 5 fps
-        Some(Duration::from_millis(1000 / TICKS_PER_SECOND))
+        // Nothing happens anymore once the game is over.
+        if self.is_over() {
+            return None;
+        }
+        Some(Duration::from_millis(
+            1000 / self.rules.ticks_per_second as u64,
+        ))
     }
 
     /// Processes a game logic tick.
     fn process_tick(&mut self) -> LiveEffect {
-        if self.winner.is_some() {
-            // Game is over.
+        if self.is_over() {
             return LiveEffect::None;
         }
+        self.refill_market();
+        if self.is_stalemate() {
+            let text = "There are no cards left to buy.".to_owned();
+            return LiveEffect::Batch(vec![
+                LiveEffect::Notify(
+                    Audience::Everyone,
+                    Notification::new(NotificationLevel::Info, text),
+                ),
+                self.finish(),
+            ]);
+        }
+        if let Some(ticks) = &mut self.end_ticks {
+            *ticks = ticks.saturating_sub(1);
+            if *ticks == 0 {
+                return self.finish();
+            }
+        }
 
         for (_player, inventory) in self.players.iter_mut() {
-            inventory.enery_fraction_ticks += 1;
-            if inventory.enery_fraction_ticks >= TICKS_PER_ENERGY {
-                inventory.enery_fraction_ticks = 0;
-                inventory.energy += 1;
+            inventory.tick(&self.rules);
+        }
+        self.gamble.tick(&self.rules);
+        self.decay_ticks += 1;
+        if self.decay_ticks >= self.ticks_per_decay {
+            self.decay_ticks = 0;
+            for card in self.market.iter_mut().flatten().flatten() {
+                card.decay();
             }
         }
+        self.bot_ticks += 1;
+        if self.bot_ticks >= self.rules.ticks(SECONDS_PER_BOT_ACTION) {
+            self.bot_ticks = 0;
+            return self.act_bots();
+        }
         LiveEffect::None
     }
 
-    /// Adds a player to the game.
-    fn join_user(&mut self, _player: UserUuid) -> LiveEffect {
-        // Players can't join the game. This only happens in setup.
-        // They turn into spectators. (TODO: Implement spectators)
+    /// Players that come back are no longer away. Everyone else can't join
+    /// the game anymore, that only happens in setup. They are spectators.
+    fn join_user(&mut self, player: UserUuid) -> LiveEffect {
+        if let Some(inventory) = self.players.get_mut(&player) {
+            inventory.away = false;
+        }
         LiveEffect::None
     }
 
+    /// Players that leave keep their inventory, the others just see them as away.
+    fn leave_user(&mut self, player: UserUuid) -> LiveEffect {
+        if let Some(inventory) = self.players.get_mut(&player) {
+            inventory.away = true;
+        }
+        LiveEffect::None
+    }
+
+    fn leave_grace_period(&self) -> Option<Duration> {
+        Some(LEAVE_GRACE_PERIOD)
+    }
+
+    /// Bots are never away.
+    fn present_users(&self) -> Vec<UserUuid> {
+        self.players
+            .iter()
+            .filter(|(uuid, data)| !data.away && self.bots.iter().all(|bot| &bot.uuid != *uuid))
+            .map(|(uuid, _)| uuid.clone())
+            .collect()
+    }
+
     fn route_id() -> &'static str {
         "pomp"
     }
 }
 
 impl PlayerData {
-    fn buy(&mut self, color: ElementColor) {
-        if self.energy >= 1 {
-            self.energy -= 1;
-            self.elements.add_element_ip(color, 1);
+    fn buy(&mut self, color: ElementColor) -> Result<(), PompRejection> {
+        if matches!(color, ElementColor::Chaos) {
+            return Err(PompRejection::ChaosNotForSale);
         }
+        if self.energy < 1 {
+            return Err(PompRejection::NotEnoughEnergy);
+        }
+        self.energy -= 1;
+        self.elements.add_element_ip(color, 1);
+        Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
-struct Card {
-    id: usize, // We need to tag the card to make it buyable.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Card {
+    pub id: usize, // We need to tag the card to make it buyable.
     color: ElementColor,
-    points: u32,
-    cost: ElementVector,
+    pub points: u32,
+    pub cost: ElementVector,
+    power: Option<Power>, // Only power cards have an effect.
+}
+
+/// Power cards start out overpriced and get cheaper while they are on the
+/// market, until they reach their minimal cost.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Power {
+    effect: PowerEffect,
+    min_cost: u32,
+}
+
+/// What a power card does for its owner, on top of points and discount.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PowerEffect {
+    FasterEnergy,                // Energy comes in faster.
+    ElementIncome(ElementColor), // One element of this color every few seconds.
+}
+
+/// Finds the tier and slot of a card on the market.
+fn find_on_market(market: &[Vec<Option<Card>>], id: usize) -> Option<(usize, usize)> {
+    market.iter().enumerate().find_map(|(tier, row)| {
+        let slot = row
+            .iter()
+            .position(|c| matches!(c, Some(c) if c.id == id))?;
+        Some((tier, slot))
+    })
+}
+
+/// Takes a card from the market and refills the slot from the deck of the
+/// same tier. Only call this for slots that have a card.
+fn take_from_market(
+    market: &mut [Vec<Option<Card>>],
+    decks: &mut [Vec<Card>],
+    tier: usize,
+    slot: usize,
+) -> Card {
+    let mut card = decks[tier].pop();
+    mem::swap(&mut market[tier][slot], &mut card);
+    card.expect("Slot on the market is empty")
 }
 
 impl Distribution<ElementColor> for Standard {
@@ -382,102 +1103,125 @@ impl Distribution<ElementColor> for Standard {
 }
 
 impl Card {
-    fn random_deck(player_count: usize) -> (Vec<Card>, Vec<Card>, Vec<Card>) {
-        // Each player is allocated 5 cards of level 1.
-        // Each player is allocated 10 cards of level 2.
-        // Each player is allocated 4 cards of level 3. (4 * 4 = 16 > 15 [win])
-        let mut deck_1 = Vec::with_capacity(player_count * 5);
-        let mut i = 0;
-        for _ in 0..(player_count * 5) {
-            deck_1.push(Card::random_level_1(i));
-            i += 1;
-        }
-        let mut deck_2 = Vec::with_capacity(player_count * 10);
-        for _ in 0..(player_count * 10) {
-            deck_2.push(Card::random_level_2(i));
-            i += 1;
-        }
-        let mut deck_3 = Vec::with_capacity(player_count * 4);
-        for _ in 0..(player_count * 4) {
-            deck_3.push(Card::random_level_3(i));
-            i += 1;
-        }
-        (deck_1, deck_2, deck_3)
-    }
-
-    fn random_level_1(id: usize) -> Card {
-        let mut rng = rand::thread_rng();
-        let x = rng.gen_range(0..5);
-        if x <= 0 {
-            // Especially cheap basic card
-            Self::fixed_cost_card(id, 0, 3)
-        } else if x <= 2 {
-            // Basic cost basic card
-            Self::fixed_cost_card(id, 0, 4)
-        } else if x <= 3 {
-            // Cheap card with 1 victory point
-            Self::fixed_cost_card(id, 1, 5)
-        } else if x <= 4 {
-            // card with 1 victory point
-            Self::fixed_cost_card(id, 1, 6)
-        } else {
-            panic!("Invalid card level");
+    /// Deals one shuffled deck for each tier of the config. Card ids are
+    /// unique across all decks.
+    fn random_decks<R: Rng>(
+        config: &DeckConfig,
+        rules: &PompRules,
+        player_count: usize,
+        next_id: &mut usize,
+        rng: &mut R,
+    ) -> Vec<Vec<Card>> {
+        let random_cards = config.random_cards();
+        let mut decks = Vec::with_capacity(config.tiers.len());
+        for (tier, random_tier) in config.tiers.iter().zip(&random_cards.tiers) {
+            let surcharge = random_cards.chaos_surcharge;
+            let mut deck =
+                Self::random_tier_cards(random_tier, surcharge, rules, player_count, next_id, rng);
+            for fixed in &tier.fixed {
+                deck.push(Card {
+                    id: *next_id,
+                    color: fixed.color,
+                    points: fixed.points,
+                    cost: fixed.cost.clone(),
+                    power: None,
+                });
+                *next_id += 1;
+            }
+            for power in &tier.power {
+                for _ in 0..power.copies {
+                    let mut card =
+                        Self::fixed_cost_card(*next_id, power.points, power.cost as usize, rng);
+                    // Power cards get cheaper until nobody pays the chaos
+                    // surcharge anymore, so they never give a wildcard.
+                    card.color = *ElementColor::BASIC.choose(rng).unwrap();
+                    card.power = Some(Power {
+                        effect: power.effect,
+                        min_cost: power.min_cost,
+                    });
+                    deck.push(card);
+                    *next_id += 1;
+                }
+            }
+            deck.shuffle(rng);
+            decks.push(deck);
         }
+        decks
     }
 
-    fn random_level_2(id: usize) -> Card {
-        let mut rng = rand::thread_rng();
-        let x = rng.gen_range(0..5);
-        if x <= 0 {
-            Self::fixed_cost_card(id, 2, 7)
-        } else if x <= 2 {
-            Self::fixed_cost_card(id, 2, 8)
-        } else if x <= 3 {
-            Self::fixed_cost_card(id, 3, 9)
-        } else if x <= 4 {
-            Self::fixed_cost_card(id, 3, 10)
-        } else {
-            panic!("Invalid card level");
+    /// The random cards of a tier, drawn from its kinds. Fixed and power cards
+    /// are not part of this, there is only one set of them per game.
+    fn random_tier_cards<R: Rng>(
+        tier: &RandomTier,
+        chaos_surcharge: u32,
+        rules: &PompRules,
+        player_count: usize,
+        next_id: &mut usize,
+        rng: &mut R,
+    ) -> Vec<Card> {
+        let random_cards = player_count * tier.cards_per_player * rules.deck_percent as usize / 100;
+        if random_cards == 0 {
+            return Vec::new();
         }
-    }
-
-    fn random_level_3(id: usize) -> Card {
-        let mut rng = rand::thread_rng();
-        let x = rng.gen_range(0..5);
-        if x <= 0 {
-            Self::fixed_cost_card(id, 4, 11)
-        } else if x <= 2 {
-            Self::fixed_cost_card(id, 4, 12)
-        } else if x <= 3 {
-            Self::fixed_cost_card(id, 5, 13)
-        } else if x <= 4 {
-            Self::fixed_cost_card(id, 5, 14)
-        } else {
-            panic!("Invalid card level");
+        let weights = tier.kinds.iter().map(|kind| kind.weight);
+        let kinds = WeightedIndex::new(weights).expect("Decks are validated on startup.");
+        let mut cards = Vec::with_capacity(random_cards);
+        for _ in 0..random_cards {
+            let kind = &tier.kinds[kinds.sample(rng)];
+            let mut card = Self::fixed_cost_card(*next_id, kind.points, kind.cost as usize, rng);
+            if matches!(card.color, ElementColor::Chaos) {
+                // Wildcards are worth more than a normal discount.
+                for _ in 0..chaos_surcharge {
+                    card.random_inc(rng);
+                }
+            }
+            cards.push(card);
+            *next_id += 1;
         }
+        cards
     }
 
     /// Randomly distributes a fixed cost across the card.
     /// The color of the card is also random.
     /// The victory points are given
-    fn fixed_cost_card(id: usize, points: u32, cost: usize) -> Card {
+    fn fixed_cost_card<R: Rng>(id: usize, points: u32, cost: usize, rng: &mut R) -> Card {
         let mut card = Card {
             id,
-            color: rand::random(),
+            color: rng.gen(),
             points,
             cost: ElementVector::default(),
+            power: None,
         };
 
         for _ in 0..cost {
-            card.random_inc();
+            card.random_inc(rng);
         }
 
         debug_assert_eq!(card.cost.total(), cost as u32);
         card
     }
 
-    fn random_inc(&mut self) {
-        let color: ElementColor = rand::random();
+    /// Makes a power card one element cheaper, taking it from the most
+    /// expensive color. Other cards never get cheaper.
+    fn decay(&mut self) {
+        let min_cost = match &self.power {
+            Some(power) => power.min_cost,
+            None => return,
+        };
+        if self.cost.total() <= min_cost {
+            return;
+        }
+        let color = ElementColor::BASIC
+            .iter()
+            .copied()
+            .max_by_key(|color| self.cost.get(*color))
+            .unwrap();
+        self.cost.remove_element_ip(color, 1);
+    }
+
+    /// Adds one element of a random basic color to the cost.
+    fn random_inc<R: Rng>(&mut self, rng: &mut R) {
+        let color = *ElementColor::BASIC.choose(rng).unwrap();
         self.cost.add_element_ip(color, 1);
     }
 }
@@ -487,17 +1231,442 @@ impl Card {
 mod test {
     use super::*;
 
+    /// A different user for every `i`.
+    fn user(i: u8) -> UserUuid {
+        let query = format!("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d9{:02x}", 0x6c + i);
+        UserUuid::from_query_string(&query).unwrap()
+    }
+
+    /// A game with seed 1 for the given number of players, who are returned
+    /// in the order they joined the lobby.
+    fn started_game(players: u8, rules: PompRules) -> (GameState, Vec<UserUuid>) {
+        let mut setup = setup::GameState::new(1);
+        let users: Vec<UserUuid> = (0..players).map(user).collect();
+        for uuid in &users {
+            let _ = setup.join_user(uuid.clone());
+        }
+        (GameState::from_setup(&setup, 1, rules), users)
+    }
+
     /// Tests that no debug asserts fail when randomly generating a card.
     #[test]
     fn test_card_random() {
         let mut rng = rand::thread_rng();
         for i in 0..100 {
-            Card::fixed_cost_card(i, rng.gen_range(0..10), rng.gen_range(1..10));
-            Card::random_level_1(i + 100);
+            let (points, cost) = (rng.gen_range(0..10), rng.gen_range(1..10));
+            Card::fixed_cost_card(i, points, cost, &mut rng);
+        }
+        let config = decks::current();
+        for players in 2..=5 {
+            let decks =
+                Card::random_decks(config, &PompRules::default(), players, &mut 0, &mut rng);
+            for (deck, tier) in decks.iter().zip(&config.tiers) {
+                let copies: usize = tier.power.iter().map(|power| power.copies).sum();
+                let expected = players * tier.cards_per_player + tier.fixed.len() + copies;
+                assert_eq!(expected, deck.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_power_cards_are_no_wildcards() {
+        let config = decks::current();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..50 {
+            let decks = Card::random_decks(config, &PompRules::default(), 4, &mut 0, &mut rng);
+            for card in decks.iter().flatten().filter(|card| card.power.is_some()) {
+                assert!(!matches!(card.color, ElementColor::Chaos));
+            }
         }
-        Card::random_deck(2);
-        Card::random_deck(3);
-        Card::random_deck(4);
-        Card::random_deck(5);
+    }
+
+    #[test]
+    fn test_same_seed_same_cards() {
+        let mut setup = setup::GameState::new(1);
+        let _ = setup.join_user(user(0));
+        let deal = |seed| {
+            serde_json::to_value(GameState::from_setup(&setup, seed, PompRules::default())).unwrap()
+        };
+        assert_eq!(deal(42), deal(42));
+        assert_ne!(deal(42), deal(43));
+    }
+
+    #[test]
+    fn test_rejections_leave_state_unchanged() {
+        let (mut state, players) = started_game(1, PompRules::default());
+        let (player, stranger) = (players[0].clone(), user(1));
+
+        let buy = state.process_remote_event(PompEvent::Buy(ElementColor::Fire), player.clone());
+        assert_eq!(Some(PompRejection::NotEnoughEnergy), buy.err());
+        let card = state.process_remote_event(PompEvent::BuyCard(9999), player.clone());
+        assert_eq!(Some(PompRejection::CardNotOnMarket), card.err());
+        let buy = state.process_remote_event(PompEvent::Buy(ElementColor::Chaos), player.clone());
+        assert_eq!(Some(PompRejection::ChaosNotForSale), buy.err());
+        let buy = state.process_remote_event(PompEvent::Buy(ElementColor::Fire), stranger);
+        assert_eq!(Some(PompRejection::NotAPlayer), buy.err());
+        assert_eq!(0, state.players[&player].elements.fire);
+    }
+
+    #[test]
+    fn test_gamble() {
+        let (mut state, players) = started_game(1, PompRules::default());
+        let player = players[0].clone();
+
+        let gamble = state.process_remote_event(PompEvent::Gamble, player.clone());
+        assert_eq!(Some(PompRejection::NotEnoughEnergy), gamble.err());
+
+        for _ in 0..state.rules.ticks(SECONDS_PER_GAMBLE_GROWTH) {
+            let _ = state.process_tick();
+        }
+        assert_eq!(GAMBLE_START_SIZE + 1, state.gamble.size);
+        state.players.get_mut(&player).unwrap().energy = GAMBLE_PRICE;
+        let gamble = state.process_remote_event(PompEvent::Gamble, player.clone());
+        assert!(matches!(
+            gamble,
+            Ok(LiveEffect::Notify(Audience::User(_), _))
+        ));
+        let inventory = &state.players[&player];
+        assert_eq!(0, inventory.energy);
+        assert_eq!(GAMBLE_START_SIZE + 1, inventory.elements.total());
+        assert_eq!(GAMBLE_START_SIZE, state.gamble.size);
+    }
+
+    #[test]
+    fn test_gamble_is_a_discount() {
+        let (mut state, players) = started_game(1, PompRules::default());
+        let player = players[0].clone();
+
+        state.players.get_mut(&player).unwrap().energy = GAMBLE_PRICE;
+        while state
+            .process_remote_event(PompEvent::Buy(ElementColor::Fire), player.clone())
+            .is_ok()
+        {}
+        let bought = state.players[&player].elements.total();
+
+        state.players.get_mut(&player).unwrap().energy = GAMBLE_PRICE;
+        assert_eq!(GAMBLE_START_SIZE, state.gamble.size);
+        assert!(state
+            .process_remote_event(PompEvent::Gamble, player.clone())
+            .is_ok());
+        let gambled = state.players[&player].elements.total() - bought;
+        assert!(gambled > bought);
+    }
+
+    #[test]
+    fn test_power_cards() {
+        let mut card = Card::fixed_cost_card(0, 0, 5, &mut rand::thread_rng());
+        card.power = Some(Power {
+            effect: PowerEffect::ElementIncome(ElementColor::Water),
+            min_cost: 3,
+        });
+        for _ in 0..5 {
+            card.decay();
+        }
+        assert_eq!(3, card.cost.total());
+
+        let rules = PompRules::default();
+        let mut player = PlayerData::new("Alice".to_owned(), &rules);
+        player.apply_power(PowerEffect::ElementIncome(ElementColor::Water), &rules);
+        for _ in 0..10 {
+            player.apply_power(PowerEffect::FasterEnergy, &rules);
+        }
+        // At most one energy per second.
+        assert_eq!(rules.ticks(1), player.ticks_per_energy);
+        for _ in 0..rules.ticks(SECONDS_PER_INCOME) {
+            player.tick(&rules);
+        }
+        assert_eq!(1, player.elements.water);
+        assert_eq!(6, player.energy);
+
+        // More than one energy per second from the start stays that way.
+        let rules = PompRules {
+            ticks_per_energy: 3,
+            ticks_per_second: 5,
+            ..PompRules::default()
+        };
+        let mut player = PlayerData::new("Bob".to_owned(), &rules);
+        player.apply_power(PowerEffect::FasterEnergy, &rules);
+        assert_eq!(3, player.ticks_per_energy);
+    }
+
+    #[test]
+    fn test_spectator_view() {
+        let (mut state, players) = started_game(1, PompRules::default());
+        let (player, spectator) = (players[0].clone(), user(1));
+
+        let _ = state.join_user(spectator.clone());
+        let view = state.user_view(&spectator);
+        assert!(view.my_inventory.is_none());
+        assert_eq!(1, view.others.len());
+        let view = state.user_view(&player);
+        assert!(view.my_inventory.is_some());
+        assert!(view.others.is_empty());
+    }
+
+    #[test]
+    fn test_bots_play() {
+        let mut setup = setup::GameState::new(1);
+        let host = user(0);
+        let _ = setup.join_user(host.clone());
+        let _ =
+            setup.process_remote_event(setup::SetupEvent::AddBot(BotKind::Greedy), host.clone());
+        let _ = setup.process_remote_event(setup::SetupEvent::AddBot(BotKind::Greedy), host);
+        let mut state = GameState::from_setup(&setup, 1, PompRules::default());
+        assert_eq!(2, state.bots.len());
+
+        // An hour of game time is plenty for two bots to finish a game.
+        for _ in 0..state.rules.ticks(3600) {
+            let _ = state.process_tick();
+        }
+        assert!(state.is_over());
+    }
+
+    #[test]
+    fn test_grace_and_ranking() {
+        let (mut state, players) = started_game(2, PompRules::default());
+        let (alice, bob) = (players[0].clone(), players[1].clone());
+        let score = state.rules.score_to_win;
+        let card = BoughtCard {
+            tier: 0,
+            color: ElementColor::Fire,
+            points: 0,
+        };
+        for (uuid, name, cards) in [(&alice, "Alice", 2), (&bob, "Bob", 1)] {
+            let player = state.players.get_mut(uuid).unwrap();
+            player.name = name.to_owned();
+            player.points = score;
+            player.energy = 2;
+            player.bought = vec![card.clone(); cards];
+        }
+
+        // Reaching the score only starts the countdown.
+        let buy = state.process_remote_event(PompEvent::Buy(ElementColor::Fire), alice.clone());
+        assert!(buy.is_ok());
+        assert!(!state.is_over());
+        let view = state.user_view(&bob);
+        assert_eq!(Some(state.rules.grace_seconds), view.seconds_left);
+        for _ in 0..state.rules.ticks(state.rules.grace_seconds) {
+            assert!(!state.is_over());
+            let _ = state.process_tick();
+        }
+        assert!(state.is_over());
+
+        // Same points, but Bob bought fewer cards.
+        let ranking = state.ranking();
+        assert_eq!(("Bob", 1), (ranking[0].name.as_str(), ranking[0].place));
+        assert_eq!(("Alice", 2), (ranking[1].name.as_str(), ranking[1].place));
+        let buy = state.process_remote_event(PompEvent::Buy(ElementColor::Fire), bob);
+        assert_eq!(Some(PompRejection::GameOver), buy.err());
+    }
+
+    #[test]
+    fn test_deck_exhaustion() {
+        let rules = PompRules {
+            refill_decks: true,
+            ..PompRules::default()
+        };
+        let (mut state, players) = started_game(1, rules);
+        let alice = players[0].clone();
+
+        // An empty first tier gets new cards with new ids.
+        state.decks[0].clear();
+        state.market[0][0] = None;
+        let _ = state.process_tick();
+        let cards_per_player = decks::current().tiers[0].cards_per_player;
+        assert_eq!(cards_per_player - 1, state.user_view(&alice).deck_sizes[0]);
+        let new_id = state.market[0][0].as_ref().unwrap().id;
+        assert!(new_id >= state.next_card_id - cards_per_player);
+
+        // Tiers without random cards and tiers the decks don't know anymore
+        // stay empty.
+        let power_tier = decks::current()
+            .tiers
+            .iter()
+            .position(|tier| tier.cards_per_player == 0)
+            .unwrap();
+        state.decks[power_tier].clear();
+        state.decks.push(Vec::new());
+        state.market.push(vec![None]);
+        let refills = state.refills;
+        let _ = state.process_tick();
+        assert!(state.decks[power_tier].is_empty());
+        assert_eq!(refills, state.refills);
+
+        // Without cards nobody can buy anything anymore.
+        state.rules.refill_decks = false;
+        state.decks.iter_mut().for_each(Vec::clear);
+        state
+            .market
+            .iter_mut()
+            .flatten()
+            .for_each(|slot| *slot = None);
+        assert!(!state.is_over());
+        let _ = state.process_tick();
+        assert!(state.is_over());
+        assert_eq!(1, state.ranking().len());
+    }
+
+    #[test]
+    fn test_refills_deal_from_the_game_decks() {
+        let config = DeckConfig::parse(
+            r#"
+            [[tier]]
+            market_slots = 1
+            cards_per_player = 2
+            kinds = [{ weight = 1, points = 7, cost = 9 }]
+        "#,
+        )
+        .unwrap();
+        let mut setup = setup::GameState::new(1);
+        let _ = setup.join_user(user(0));
+        let rules = PompRules {
+            refill_decks: true,
+            ..PompRules::default()
+        };
+        let state = GameState::deal(&setup, 1, rules, &config);
+
+        // Restored from disk while other decks are installed.
+        assert_ne!(config.tiers.len(), decks::current().tiers.len());
+        let snapshot = serde_json::to_value(&state).unwrap();
+        let mut state: GameState = serde_json::from_value(snapshot).unwrap();
+        state.decks[0].clear();
+        state.market[0][0] = None;
+        let _ = state.process_tick();
+        assert_eq!(1, state.refills);
+        let refilled = state.decks[0].iter().chain(state.market[0][0].as_ref());
+        assert_eq!(2, refilled.clone().count());
+        for card in refilled {
+            assert_eq!((7, 9), (card.points, card.cost.total()));
+        }
+    }
+
+    #[test]
+    fn test_reserve() {
+        let (mut state, players) = started_game(2, PompRules::default());
+        let (alice, bob) = (players[0].clone(), players[1].clone());
+
+        for slot in 0..RESERVE_LIMIT {
+            let id = state.market[0][slot].as_ref().unwrap().id;
+            let reserve = state.process_remote_event(PompEvent::Reserve(id), alice.clone());
+            assert!(reserve.is_ok());
+            assert_ne!(Some(id), state.market[0][slot].as_ref().map(|c| c.id));
+        }
+        let id = state.market[1][0].as_ref().unwrap().id;
+        let reserve = state.process_remote_event(PompEvent::Reserve(id), alice.clone());
+        assert_eq!(Some(PompRejection::ReserveFull), reserve.err());
+        assert_eq!(3, state.players[&alice].elements.chaos);
+
+        // Bob only sees the tiers of Alice's reserved cards.
+        let view = state.user_view(&bob);
+        assert!(view.my_reserved.is_empty());
+        assert_eq!(vec![0, 0, 0], view.others[0].reserved_tiers);
+        let reserved = state.user_view(&alice).my_reserved;
+        assert_eq!(3, reserved.len());
+
+        let id = reserved[0].id;
+        let buy = state.process_remote_event(PompEvent::BuyReserved(id), bob.clone());
+        assert_eq!(Some(PompRejection::CardNotReserved), buy.err());
+        state
+            .players
+            .get_mut(&alice)
+            .unwrap()
+            .elements
+            .add_ip(&reserved[0].cost);
+        let buy = state.process_remote_event(PompEvent::BuyReserved(id), alice.clone());
+        assert!(buy.is_ok());
+        assert_eq!(2, state.players[&alice].reserved.len());
+        assert_eq!(1, state.players[&alice].discount.total());
+    }
+
+    #[test]
+    fn test_patrons() {
+        let patron = Patron::random(0, &mut rand::thread_rng());
+        assert!(matches!(patron.requirement.total(), 8 | 9));
+
+        let two_colors = ElementVector {
+            fire: 4,
+            water: 4,
+            ..Default::default()
+        };
+        let mut patrons = vec![
+            Patron {
+                requirement: two_colors.clone(),
+                ..patron.clone()
+            },
+            Patron {
+                id: 1,
+                requirement: ElementVector {
+                    fire: 3,
+                    plant: 3,
+                    earth: 3,
+                    ..Default::default()
+                },
+                ..patron
+            },
+        ];
+        let mut alice = PlayerData::new("Alice".to_owned(), &PompRules::default());
+        alice.discount.chaos = 12;
+        // Wildcards don't count for patrons.
+        let effect = award_patron(&mut patrons, &mut alice);
+        assert!(matches!(effect, LiveEffect::None));
+
+        alice.discount = two_colors.clone();
+        let effect = award_patron(&mut patrons, &mut alice);
+        assert!(matches!(effect, LiveEffect::Notify(Audience::Everyone, _)));
+        assert_eq!(PATRON_POINTS, alice.points);
+        assert_eq!(Some("Alice"), patrons[0].owner.as_deref());
+        let effect = award_patron(&mut patrons, &mut alice);
+        assert!(matches!(effect, LiveEffect::None));
+
+        // Nobody else gets the same patron.
+        let mut bob = PlayerData::new("Bob".to_owned(), &PompRules::default());
+        bob.discount = two_colors;
+        let effect = award_patron(&mut patrons, &mut bob);
+        assert!(matches!(effect, LiveEffect::None));
+        assert_eq!(0, bob.points);
+    }
+
+    #[test]
+    fn test_chaos_wildcards() {
+        let cost = ElementVector {
+            fire: 3,
+            water: 2,
+            ..Default::default()
+        };
+        let elements = ElementVector {
+            fire: 1,
+            water: 3,
+            ..Default::default()
+        };
+        let discount = ElementVector {
+            fire: 1,
+            chaos: 1,
+            ..Default::default()
+        };
+        // The fire discount and one element leave one fire for the wildcard.
+        let payment = cost.payment(&elements, &discount).unwrap();
+        assert_eq!((1, 2), (payment.fire, payment.water));
+
+        let discount = ElementVector {
+            chaos: 3,
+            ..Default::default()
+        };
+        // Two wildcards are needed for fire, the last one saves another fire.
+        let payment = cost.payment(&elements, &discount).unwrap();
+        assert_eq!((0, 2), (payment.fire, payment.water));
+
+        let discount = ElementVector {
+            chaos: 1,
+            ..Default::default()
+        };
+        assert!(cost.payment(&elements, &discount).is_none());
+
+        // Chaos elements in hand are used up when the wildcards are not enough.
+        let elements = ElementVector {
+            chaos: 1,
+            ..elements
+        };
+        let payment = cost.payment(&elements, &discount).unwrap();
+        assert_eq!((1, 2, 1), (payment.fire, payment.water, payment.chaos));
     }
 }
@@ -0,0 +1,58 @@
+//! The route table of this server.
+//!
+//! To add a new game, implement `SharedLiveState` for it and register it here.
+//! The broker takes care of spawning one actor per distinct route parameter.
+//! Every route is persistent, so its live state survives a restart.
+
+use crate::{
+    event_log::{self, LogRecord, Replay, ReplayError},
+    game::{self, SharedLiveState},
+    pomp, setup, wedding,
+};
+
+/// Everything that needs to know all routes: the broker serves them and
+/// `replay` reads the logs they write.
+pub trait RouteTable {
+    fn register<S: SharedLiveState>(&mut self);
+}
+
+pub fn register_all(table: &mut impl RouteTable) {
+    table.register::<wedding::WeddingData>();
+    table.register::<setup::GameState>();
+    table.register::<pomp::GameState>();
+}
+
+type Replayer = fn(&[LogRecord]) -> Result<Replay<serde_json::Value>, ReplayError>;
+
+/// Collects a replayer for every route in `register_all`.
+#[derive(Default)]
+struct Replayers(Vec<(&'static str, Replayer)>);
+
+impl RouteTable for Replayers {
+    fn register<S: SharedLiveState>(&mut self) {
+        self.0.push((S::route_pattern(), replay_as::<S>));
+    }
+}
+
+/// Replays an event log with the live state of the route it was written for.
+pub fn replay(records: &[LogRecord]) -> Result<Replay<serde_json::Value>, ReplayError> {
+    let mut replayers = Replayers::default();
+    register_all(&mut replayers);
+    let route = event_log::logged_route(records).ok_or(ReplayError::MissingInit)?;
+    for (pattern, replayer) in replayers.0.iter() {
+        if game::match_route(pattern, route).is_some() {
+            return replayer(records);
+        }
+    }
+    Err(ReplayError::UnknownRoute(route.to_owned()))
+}
+
+fn replay_as<S: SharedLiveState>(
+    records: &[LogRecord],
+) -> Result<Replay<serde_json::Value>, ReplayError> {
+    let replay = event_log::replay::<S>(records)?;
+    Ok(Replay {
+        state: serde_json::to_value(&replay.state).expect("Serde error while encoding to json."),
+        divergences: replay.divergences,
+    })
+}
@@ -1,8 +1,11 @@
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    game::{LiveEffect, RemoteEvent, SharedLiveState, UserUuid, UserView},
-    pomp,
+    bots::BotKind,
+    game::{GameId, LiveEffect, RemoteEvent, SharedLiveState, UserUuid, UserView},
+    pomp::{self, PompRules},
 };
 /// Setting up a game of pomp. When you are done, you can forward all the
 /// connected players to the pomp LiveState.
@@ -11,24 +14,72 @@ use rust_elm_typegen::ElmExport;
 impl ElmExport for SetupPlayerView {}
 impl ElmExport for PlayerSetupData {}
 impl ElmExport for SetupEvent {}
+impl ElmExport for SetupRejection {}
+
+/// Players that don't come back within this time are removed from the lobby.
+const LEAVE_GRACE_PERIOD: Duration = Duration::from_secs(5);
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct GameState {
     // This is intentionally not a HashMap, because we need an ordering.
     pub data: Vec<(UserUuid, PlayerSetupData)>,
     is_started: bool,
+    // The game we are setting up. Players are forwarded to /pomp/{game_id}.
+    game_id: GameId,
+    // Seed for the cards, a random one is picked when the game starts.
+    seed: Option<u32>,
+    rules: PompRules,  // Only the host can change them.
+    bots_added: usize, // Numbers the bots, so their uuids are unique.
 }
 
-#[derive(Debug, Clone, Serialize)]
+impl GameState {
+    pub fn new(game_id: GameId) -> Self {
+        GameState {
+            game_id,
+            ..GameState::default()
+        }
+    }
+
+    /// Bots get a seat without joining, they are numbered so their uuids
+    /// and names are unique.
+    pub fn add_bot(&mut self, kind: BotKind) {
+        self.bots_added += 1;
+        let name = format!("{} Bot {}", kind.name(), self.bots_added);
+        let uuid = UserUuid::synthetic(&format!("bot-{}-{}", self.game_id, self.bots_added));
+        self.data.push((
+            uuid,
+            PlayerSetupData {
+                is_ready: true,
+                name,
+                bot: Some(kind),
+            },
+        ));
+    }
+
+    /// The host is the first human in the list. Bots are never host, so
+    /// there is a new one when the host leaves.
+    fn host_index(&self) -> Option<usize> {
+        self.data.iter().position(|(_, data)| data.bot.is_none())
+    }
+
+    fn game_route(&self) -> String {
+        format!("/pomp/{}", self.game_id)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerSetupData {
     is_ready: bool,
     pub name: String,
+    pub bot: Option<BotKind>, // Bots are always ready.
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct SetupPlayerView {
     data: Vec<PlayerSetupData>,
     my_index: isize,
+    seed: Option<u32>,
+    rules: PompRules,
 }
 
 impl UserView for SetupPlayerView {}
@@ -38,9 +89,23 @@ impl UserView for SetupPlayerView {}
 pub enum SetupEvent {
     SetName(String),
     SetReady(bool),
+    SetSeed(Option<u32>), // None means a random seed, host only.
+    SetRules(PompRules),  // Only the host, the first human, can do this.
+    AddBot(BotKind),      // Host only as well.
+    RemoveBot(usize),     // Index of the bot in the player list, host only.
     StartGame,
 }
 
+/// Why a `SetupEvent` was not accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SetupRejection {
+    NotInLobby, // The sender has no seat in this lobby.
+    NotHost,
+    InvalidRules,
+    NotABot, // Only bots can be removed from the lobby.
+    AlreadyStarted,
+}
+
 impl RemoteEvent for SetupEvent {
     fn deserialize(s: &str) -> Result<Self, serde_json::Error> {
         serde_json::from_str(s)
@@ -50,6 +115,17 @@ impl RemoteEvent for SetupEvent {
 impl SharedLiveState for GameState {
     type View = SetupPlayerView;
     type Event = SetupEvent;
+    type Params = GameId;
+    type Timer = ();
+    type Rejection = SetupRejection;
+
+    fn route_pattern() -> &'static str {
+        "/pomp/{id}/setup"
+    }
+
+    fn from_route(game_id: &GameId) -> Option<Self> {
+        Some(GameState::new(*game_id))
+    }
 
     /// Extract information that is relevant for one player and hide the rest.
     fn user_view(&self, player: &UserUuid) -> SetupPlayerView {
@@ -62,33 +138,79 @@ impl SharedLiveState for GameState {
             data.push(setup_data.clone());
         }
 
-        SetupPlayerView { data, my_index }
+        SetupPlayerView {
+            data,
+            my_index,
+            seed: self.seed,
+            rules: self.rules.clone(),
+        }
     }
 
     /// Process a remote event.
-    fn process_remote_event(&mut self, event: SetupEvent, sender: UserUuid) -> LiveEffect {
-        let data = self.data.iter_mut().find(|(uuid, _)| uuid == &sender);
-        if let Some(data) = data {
-            match event {
-                SetupEvent::SetName(name) => data.1.name = name,
-                SetupEvent::SetReady(ready) => data.1.is_ready = ready,
-                SetupEvent::StartGame => {
-                    if self.is_started {
-                        return LiveEffect::None;
+    fn process_remote_event(
+        &mut self,
+        event: SetupEvent,
+        sender: UserUuid,
+    ) -> Result<LiveEffect, SetupRejection> {
+        let index = self.data.iter().position(|(uuid, _)| uuid == &sender);
+        let index = index.ok_or(SetupRejection::NotInLobby)?;
+        let is_host = self.host_index() == Some(index);
+        let data = &mut self.data[index];
+        match event {
+            SetupEvent::SetName(name) => data.1.name = name,
+            SetupEvent::SetReady(ready) => data.1.is_ready = ready,
+            SetupEvent::SetSeed(seed) => {
+                if !is_host {
+                    return Err(SetupRejection::NotHost);
+                }
+                self.seed = seed;
+            }
+            SetupEvent::SetRules(rules) => {
+                if !is_host {
+                    return Err(SetupRejection::NotHost);
+                }
+                if !rules.is_valid() {
+                    return Err(SetupRejection::InvalidRules);
+                }
+                self.rules = rules;
+            }
+            SetupEvent::AddBot(kind) => {
+                if !is_host {
+                    return Err(SetupRejection::NotHost);
+                }
+                self.add_bot(kind);
+            }
+            SetupEvent::RemoveBot(bot_index) => {
+                if !is_host {
+                    return Err(SetupRejection::NotHost);
+                }
+                match self.data.get(bot_index) {
+                    Some((_, data)) if data.bot.is_some() => {
+                        self.data.remove(bot_index);
                     }
-                    self.is_started = true;
-                    let game = pomp::GameState::from_setup(self);
-                    return LiveEffect::LiveRedirectInit("/pomp/1".to_owned(), Box::new(game));
+                    _ => return Err(SetupRejection::NotABot),
                 }
             }
+            SetupEvent::StartGame => {
+                if self.is_started {
+                    return Err(SetupRejection::AlreadyStarted);
+                }
+                self.is_started = true;
+                let seed = self.seed.unwrap_or_else(rand::random);
+                let game = pomp::GameState::from_setup(self, seed, self.rules.clone());
+                return Ok(LiveEffect::LiveRedirectInit(
+                    self.game_route(),
+                    Box::new(game),
+                ));
+            }
         }
-        LiveEffect::None
+        Ok(LiveEffect::None)
     }
 
     /// This happens every time a connection is established.
     fn join_user(&mut self, player: UserUuid) -> LiveEffect {
         if self.is_started {
-            return LiveEffect::LiveRedirect("/pomp/1".to_owned());
+            return LiveEffect::LiveRedirect(self.game_route());
         }
 
         // Check if this uuid is already inside.
@@ -96,11 +218,13 @@ impl SharedLiveState for GameState {
             return LiveEffect::None;
         }
 
+        let name = random_name(&player);
         self.data.push((
             player,
             PlayerSetupData {
                 is_ready: false,
-                name: random_name(),
+                name,
+                bot: None,
             },
         ));
         // TODO: Check if there is already a game running. If so, redirect the
@@ -108,13 +232,44 @@ impl SharedLiveState for GameState {
         LiveEffect::None
     }
 
+    /// Absent players are dropped from the lobby, so the game does not wait
+    /// for them to get ready.
+    fn leave_user(&mut self, player: UserUuid) -> LiveEffect {
+        if !self.is_started {
+            self.data.retain(|(uuid, _)| uuid != &player);
+        }
+        LiveEffect::None
+    }
+
+    fn leave_grace_period(&self) -> Option<Duration> {
+        Some(LEAVE_GRACE_PERIOD)
+    }
+
+    /// Bots never connect, so they never leave either.
+    fn present_users(&self) -> Vec<UserUuid> {
+        self.data
+            .iter()
+            .filter(|(_, data)| data.bot.is_none())
+            .map(|(uuid, _)| uuid.clone())
+            .collect()
+    }
+
     fn route_id() -> &'static str {
         "setup"
     }
 }
 
-fn random_name() -> String {
-    use rand::Rng;
+/// The name only looks random. It is picked with the uuid as seed, so the same
+/// player always gets the same name and replaying the event log gives the
+/// same lobby.
+fn random_name(player: &UserUuid) -> String {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    let seed = player
+        .to_string()
+        .bytes()
+        .fold(0u64, |seed, b| seed.wrapping_mul(31).wrapping_add(b as u64));
+    let mut rng = StdRng::seed_from_u64(seed);
 
     // Positive adjectives
     const ADJECTIVE: [&str; 21] = [
@@ -157,9 +312,9 @@ fn random_name() -> String {
     // Combine a random sentiment, color and animal into a single name.
     format!(
         "{} {} {}",
-        ADJECTIVE[rand::thread_rng().gen_range(0..ADJECTIVE.len())],
-        COLOR[rand::thread_rng().gen_range(0..COLOR.len())],
-        ANIMAL[rand::thread_rng().gen_range(0..ANIMAL.len())]
+        ADJECTIVE[rng.gen_range(0..ADJECTIVE.len())],
+        COLOR[rng.gen_range(0..COLOR.len())],
+        ANIMAL[rng.gen_range(0..ANIMAL.len())]
     )
 }
 
@@ -170,7 +325,10 @@ mod tests {
 
     #[test]
     fn test_random_name() {
-        assert!(!random_name().is_empty());
+        let player =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        assert!(!random_name(&player).is_empty());
+        assert_eq!(random_name(&player), random_name(&player));
     }
 
     #[test]
@@ -180,4 +338,96 @@ mod tests {
         let s = serde_json::to_string(&e).unwrap();
         assert_eq!("\"StartGame\"", s);
     }
+
+    #[test]
+    fn test_leave_drops_player_from_lobby() {
+        let mut state = GameState::new(1);
+        let player =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        let _ = state.join_user(player.clone());
+        assert_eq!(1, state.data.len());
+        let _ = state.leave_user(player);
+        assert!(state.data.is_empty());
+    }
+
+    #[test]
+    fn test_only_host_sets_rules_and_seed() {
+        let mut state = GameState::new(1);
+        let host =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        let guest =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96d").unwrap();
+        let _ = state.join_user(host.clone());
+        let _ = state.join_user(guest.clone());
+        let rules = PompRules {
+            score_to_win: 10,
+            ..PompRules::default()
+        };
+
+        let set = state.process_remote_event(SetupEvent::SetRules(rules.clone()), guest.clone());
+        assert_eq!(Some(SetupRejection::NotHost), set.err());
+        let seed = state.process_remote_event(SetupEvent::SetSeed(Some(7)), guest.clone());
+        assert_eq!(Some(SetupRejection::NotHost), seed.err());
+        assert_eq!(None, state.user_view(&guest).seed);
+        let invalid = PompRules {
+            ticks_per_second: 0,
+            ..rules.clone()
+        };
+        let set = state.process_remote_event(SetupEvent::SetRules(invalid), host.clone());
+        assert_eq!(Some(SetupRejection::InvalidRules), set.err());
+        let set = state.process_remote_event(SetupEvent::SetRules(rules.clone()), host);
+        assert!(set.is_ok());
+        assert_eq!(rules, state.user_view(&guest).rules);
+    }
+
+    #[test]
+    fn test_add_and_remove_bots() {
+        let mut state = GameState::new(1);
+        let host =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        let _ = state.join_user(host.clone());
+        let add = state.process_remote_event(SetupEvent::AddBot(BotKind::Greedy), host.clone());
+        assert!(add.is_ok());
+        assert_eq!(2, state.data.len());
+        assert!(state.data[1].1.is_ready);
+        assert_eq!(vec![host.clone()], state.present_users());
+
+        let remove = state.process_remote_event(SetupEvent::RemoveBot(0), host.clone());
+        assert_eq!(Some(SetupRejection::NotABot), remove.err());
+        let remove = state.process_remote_event(SetupEvent::RemoveBot(1), host);
+        assert!(remove.is_ok());
+        assert_eq!(1, state.data.len());
+    }
+
+    #[test]
+    fn test_next_human_becomes_host() {
+        let mut state = GameState::new(1);
+        let host =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96c").unwrap();
+        let guest =
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96d").unwrap();
+        let _ = state.join_user(host.clone());
+        let _ = state.process_remote_event(SetupEvent::AddBot(BotKind::Greedy), host.clone());
+        let _ = state.join_user(guest.clone());
+        let add = state.process_remote_event(SetupEvent::AddBot(BotKind::Greedy), guest.clone());
+        assert_eq!(Some(SetupRejection::NotHost), add.err());
+
+        // The bot moves to the front, but the guest takes over.
+        let _ = state.leave_user(host);
+        assert!(state.data[0].1.bot.is_some());
+        let remove = state.process_remote_event(SetupEvent::RemoveBot(0), guest);
+        assert!(remove.is_ok());
+    }
+
+    #[test]
+    fn test_redirect_follows_game_id() {
+        let mut state = GameState::new(3);
+        state.is_started = true;
+        match state.join_user(
+            UserUuid::from_query_string("uuid=d3fdf683-9fa0-4d90-b93b-0ecec8c9d96d").unwrap(),
+        ) {
+            LiveEffect::LiveRedirect(route) => assert_eq!("/pomp/3", route),
+            _ => panic!("Expected a redirect into the running game"),
+        }
+    }
 }
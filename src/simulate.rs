@@ -0,0 +1,132 @@
+//! Headless Pomp games between bots, for balancing the decks. The games run
+//! on `pomp::GameState` directly and tick as fast as possible, there are no
+//! actors or websockets involved. Every game is one line of CSV.
+
+use std::io::{self, Write};
+
+use crate::{
+    bots::BotKind,
+    decks,
+    game::SharedLiveState,
+    pomp::{self, ElementColor, PompRules},
+    setup,
+};
+
+/// Games that take longer than this are stopped without a winner.
+const MAX_SECONDS: u32 = 60 * 60;
+
+/// Column order of the points by color.
+const COLORS: [ElementColor; 5] = [
+    ElementColor::Fire,
+    ElementColor::Plant,
+    ElementColor::Water,
+    ElementColor::Earth,
+    ElementColor::Chaos,
+];
+
+/// Statistics of one finished game.
+#[derive(Debug)]
+pub struct GameStats {
+    pub seed: u32,
+    pub seconds: u32,
+    /// Seat of the winner in the lobby, counted from 0. When players share
+    /// the first place, this is the one that comes first in the ranking.
+    pub winner_position: Option<usize>,
+    /// Cards bought per tier, this includes reserved cards that were bought.
+    pub bought_per_tier: Vec<u32>,
+    /// Points of bought cards by card color, in the order of `COLORS`.
+    pub points_by_color: [u32; 5],
+}
+
+/// Plays one game between the bots, seated in the given order.
+pub fn simulate_game(bots: &[BotKind], seed: u32, rules: PompRules) -> GameStats {
+    let mut lobby = setup::GameState::new(0);
+    for kind in bots {
+        lobby.add_bot(*kind);
+    }
+    let mut game = pomp::GameState::from_setup(&lobby, seed, rules);
+
+    let max_ticks = game.rules().ticks_per_second * MAX_SECONDS;
+    let mut ticks = 0;
+    while !game.is_over() && ticks < max_ticks {
+        let _ = game.process_tick();
+        ticks += 1;
+    }
+
+    let winner_position = game.ranking().first().and_then(|winner| {
+        lobby
+            .data
+            .iter()
+            .position(|(_, data)| data.name == winner.name)
+    });
+    let mut bought_per_tier = vec![0; decks::current().tiers.len()];
+    let mut points_by_color = [0; 5];
+    for card in game.bought_cards() {
+        bought_per_tier[card.tier] += 1;
+        let color = COLORS.iter().position(|c| *c == card.color).unwrap();
+        points_by_color[color] += card.points;
+    }
+
+    GameStats {
+        seed,
+        seconds: ticks / game.rules().ticks_per_second,
+        winner_position,
+        bought_per_tier,
+        points_by_color,
+    }
+}
+
+/// `pomp simulate <games> [players]` plays greedy bots against each other
+/// with the default rules. Game `n` uses seed `n`, so an interesting game can
+/// be watched again by setting its seed in the lobby.
+pub fn simulate_command(games: u32, players: usize) -> io::Result<()> {
+    let bots = vec![BotKind::Greedy; players];
+    let tiers = decks::current().tiers.len();
+
+    let stdout = io::stdout();
+    let mut out = io::BufWriter::new(stdout.lock());
+    let mut header = vec!["seed".to_owned(), "seconds".to_owned(), "winner".to_owned()];
+    header.extend((1..=tiers).map(|tier| format!("tier{}_bought", tier)));
+    header.extend(
+        COLORS
+            .iter()
+            .map(|color| format!("{:?}_points", color).to_lowercase()),
+    );
+    writeln!(out, "{}", header.join(","))?;
+
+    for seed in 0..games {
+        let stats = simulate_game(&bots, seed, PompRules::default());
+        let mut row = vec![
+            stats.seed.to_string(),
+            stats.seconds.to_string(),
+            stats
+                .winner_position
+                .map(|p| p.to_string())
+                .unwrap_or_default(),
+        ];
+        row.extend(stats.bought_per_tier.iter().map(u32::to_string));
+        row.extend(stats.points_by_color.iter().map(u32::to_string));
+        writeln!(out, "{}", row.join(","))?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulate_game() {
+        let rules = PompRules::default();
+        let stats = simulate_game(&[BotKind::Greedy, BotKind::Greedy], 7, rules.clone());
+        assert!(stats.winner_position.is_some());
+        assert!(stats.seconds < MAX_SECONDS);
+        let card_points: u32 = stats.points_by_color.iter().sum();
+        assert!(card_points > 0);
+
+        // Same seed, same game.
+        let again = simulate_game(&[BotKind::Greedy, BotKind::Greedy], 7, rules);
+        assert_eq!(stats.seconds, again.seconds);
+        assert_eq!(stats.bought_per_tier, again.bought_per_tier);
+    }
+}
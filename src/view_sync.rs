@@ -0,0 +1,188 @@
+//! Keeps track of the view a client has, so the next view can be sent as a
+//! JSON Patch (RFC 6902) against it instead of in full.
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// After this many patches the client gets a full snapshot again.
+const PATCHES_PER_SNAPSHOT: u32 = 50;
+
+/// How the `UserView` gets to the client. Usually this is a JSON Patch
+/// against the view with version `base`, every now and then and after
+/// navigation the client gets the full view instead.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum LiveStateFrame<'a> {
+    Snapshot {
+        route: &'static str,
+        version: u64,
+        data: &'a Value,
+    },
+    Patch {
+        route: &'static str,
+        base: u64,
+        version: u64,
+        patch: &'a json_patch::Patch,
+    },
+}
+
+/// The view one websocket client has right now. Versions count up with every
+/// frame, so the client notices when it missed one and asks for a resync.
+#[derive(Default)]
+pub struct ViewSync {
+    last_view: Option<(&'static str, Value)>,
+    version: u64,
+    patches_since_snapshot: u32,
+}
+
+impl ViewSync {
+    /// Forgets the view, e.g. after navigation. The next update is a snapshot.
+    pub fn reset(&mut self) {
+        self.last_view = None;
+    }
+
+    /// The frame that brings the client to the new view, `None` if nothing
+    /// changed.
+    pub fn update(&mut self, route: &'static str, view: Value) -> Option<String> {
+        let (patch, fits) = match &self.last_view {
+            Some((last_route, last_view)) if *last_route == route => {
+                let patch = json_patch::diff(last_view, &view);
+                // json_patch uses "/" for the whole document, which clients
+                // can't apply, and its diff is wrong when a value switches
+                // between object and array. So the patch has to prove itself.
+                let mut patched = last_view.clone();
+                let fits = json_patch::patch(&mut patched, &patch).is_ok() && patched == view;
+                (patch, fits)
+            }
+            _ => {
+                self.last_view = Some((route, view));
+                return self.snapshot();
+            }
+        };
+        if patch.0.is_empty() {
+            return None;
+        }
+        self.last_view = Some((route, view));
+
+        if !fits || self.patches_since_snapshot >= PATCHES_PER_SNAPSHOT {
+            return self.snapshot();
+        }
+        self.patches_since_snapshot += 1;
+        self.version += 1;
+        let frame = LiveStateFrame::Patch {
+            route,
+            base: self.version - 1,
+            version: self.version,
+            patch: &patch,
+        };
+        Some(serde_json::to_string(&frame).expect("Serde error while encoding to json."))
+    }
+
+    /// The full view the client should have. This starts a new chain of
+    /// patches, so it also answers a `Resync`.
+    pub fn snapshot(&mut self) -> Option<String> {
+        let (route, data) = self.last_view.as_ref()?;
+        self.version += 1;
+        self.patches_since_snapshot = 0;
+        let frame = LiveStateFrame::Snapshot {
+            route,
+            version: self.version,
+            data,
+        };
+        Some(serde_json::to_string(&frame).expect("Serde error while encoding to json."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const ROUTE: &str = "/test";
+
+    /// Does what the client does with a frame: snapshots replace the view,
+    /// patches only apply to the version they were made for. `Err` means the
+    /// client asks for a resync.
+    fn receive(client: &mut Option<(u64, Value)>, frame: &str) -> Result<(), ()> {
+        let frame: Value = serde_json::from_str(frame).unwrap();
+        let version = frame["version"].as_u64().unwrap();
+        if frame.get("data").is_some() {
+            *client = Some((version, frame["data"].clone()));
+            return Ok(());
+        }
+        let (client_version, view) = client.as_mut().ok_or(())?;
+        if frame["base"].as_u64() != Some(*client_version) {
+            return Err(());
+        }
+        let patch: json_patch::Patch = serde_json::from_value(frame["patch"].clone()).unwrap();
+        json_patch::patch(view, &patch).map_err(|_| ())?;
+        *client_version = version;
+        Ok(())
+    }
+
+    fn is_patch(frame: &str) -> bool {
+        serde_json::from_str::<Value>(frame)
+            .unwrap()
+            .get("patch")
+            .is_some()
+    }
+
+    #[test]
+    fn test_patches_round_trip() {
+        let mut sync = ViewSync::default();
+        let mut client = None;
+        let first = sync.update(ROUTE, json!({"cards": [1, 2]})).unwrap();
+        assert!(!is_patch(&first));
+        receive(&mut client, &first).unwrap();
+
+        let appended = json!({"cards": [1, 2, 3]});
+        let frame = sync.update(ROUTE, appended.clone()).unwrap();
+        assert!(is_patch(&frame));
+        receive(&mut client, &frame).unwrap();
+        assert_eq!(Some(&appended), client.as_ref().map(|(_, view)| view));
+
+        let removed = json!({"cards": [1, 3]});
+        let frame = sync.update(ROUTE, removed.clone()).unwrap();
+        assert!(is_patch(&frame));
+        receive(&mut client, &frame).unwrap();
+        assert_eq!(Some(&removed), client.as_ref().map(|(_, view)| view));
+
+        assert!(sync.update(ROUTE, removed).is_none());
+    }
+
+    #[test]
+    fn test_root_replace_is_a_snapshot() {
+        let mut sync = ViewSync::default();
+        let mut client = None;
+        receive(&mut client, &sync.update(ROUTE, json!({"a": 1})).unwrap()).unwrap();
+
+        // "/" for the whole document and a diff that turns objects into arrays.
+        for view in [json!("Over"), json!({"a": 2}), json!([1]), json!({"a": 3})] {
+            let frame = sync.update(ROUTE, view.clone()).unwrap();
+            receive(&mut client, &frame).unwrap();
+            assert_eq!(Some(&view), client.as_ref().map(|(_, view)| view));
+        }
+        let frame = sync.update(ROUTE, json!([1])).unwrap();
+        assert!(!is_patch(&frame));
+    }
+
+    #[test]
+    fn test_missed_frame_needs_resync() {
+        let mut sync = ViewSync::default();
+        let mut client = None;
+        receive(&mut client, &sync.update(ROUTE, json!({"a": 1})).unwrap()).unwrap();
+
+        // The client never sees this frame.
+        let _lost = sync.update(ROUTE, json!({"a": 2})).unwrap();
+        let frame = sync.update(ROUTE, json!({"a": 3})).unwrap();
+        assert_eq!(Err(()), receive(&mut client, &frame));
+
+        receive(&mut client, &sync.snapshot().unwrap()).unwrap();
+        let frame = sync.update(ROUTE, json!({"a": 4})).unwrap();
+        receive(&mut client, &frame).unwrap();
+        assert_eq!(
+            Some(&json!({"a": 4})),
+            client.as_ref().map(|(_, view)| view)
+        );
+    }
+}
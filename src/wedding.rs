@@ -4,17 +4,40 @@
 //! library abstraction.
 
 use std::collections::{HashMap, HashSet};
-use std::time::Instant;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
 
 use crate::wedding_types::*;
 
-use crate::game::{LiveEffect, RemoteEvent, SharedLiveState, UserUuid, UserView};
+use crate::game::{
+    Audience, LiveEffect, Notification, NotificationLevel, RemoteEvent, SharedLiveState, TimerId,
+    UserUuid, UserView,
+};
 
 const BIG_CONSTANT: usize = 99999999;
 const HIGHSCORE_LIMIT: usize = 10;
+/// How long guests can still vote after the host asked to close voting.
+const VOTING_COUNTDOWN: Duration = Duration::from_secs(20);
+/// Phones go to sleep a lot, so we wait a bit before someone counts as offline.
+const LEAVE_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Serialize, Deserialize)]
+pub enum WeddingTimer {
+    CloseVoting(usize),
+}
+
+fn close_voting_timer(question: usize) -> TimerId {
+    TimerId(format!("close-voting-{}", question))
+}
 
+#[derive(Serialize, Deserialize)]
 pub struct WeddingData {
     players: HashMap<UserUuid, PlayerName>,
+    // Everyone with an open connection, named or not. Not persisted, because
+    // nobody is connected after a restart.
+    #[serde(skip)]
+    online: HashSet<UserUuid>,
     hosts: HashSet<UserUuid>,
     projectors: HashSet<UserUuid>,
     questions: Vec<Question>,
@@ -28,6 +51,7 @@ impl Default for WeddingData {
     fn default() -> Self {
         WeddingData {
             players: HashMap::new(),
+            online: HashSet::new(),
             hosts: HashSet::new(),
             projectors: HashSet::new(),
             questions: vec![
@@ -43,10 +67,14 @@ impl Default for WeddingData {
     }
 }
 
+#[derive(Serialize, Deserialize)]
 struct Question {
     pub text: String,
     pub state: QuestionState,
-    guesses: HashMap<UserUuid, (Espoused, Instant)>,
+    // The number says in which order the guesses came in. This is not a
+    // timestamp, so replaying the event log gives the same scores.
+    guesses: HashMap<UserUuid, (Espoused, usize)>,
+    next_guess: usize,
     bride_guesses: usize, // Cached
     groom_guesses: usize, // Cached
 }
@@ -57,6 +85,7 @@ impl Question {
             text: text.to_owned(),
             state: QuestionState::GuestsCanVote,
             guesses: HashMap::new(),
+            next_guess: 0,
             bride_guesses: 0,
             groom_guesses: 0,
         }
@@ -86,7 +115,8 @@ impl Question {
             }
         }
         // Add the new guess to the map and to the count.
-        self.guesses.insert(user, (guess, Instant::now()));
+        self.guesses.insert(user, (guess, self.next_guess));
+        self.next_guess += 1;
         if guess == Espoused::Bride {
             self.bride_guesses += 1;
         } else {
@@ -95,6 +125,7 @@ impl Question {
     }
 }
 /// Stores name, score and other data for a player.
+#[derive(Serialize, Deserialize)]
 struct PlayerName(String);
 
 impl UserView for WeddingView {}
@@ -108,6 +139,17 @@ impl RemoteEvent for WeddingEvent {
 impl SharedLiveState for WeddingData {
     type View = WeddingView;
     type Event = WeddingEvent;
+    type Params = ();
+    type Timer = WeddingTimer;
+    type Rejection = WeddingRejection;
+
+    fn route_pattern() -> &'static str {
+        "/wedding"
+    }
+
+    fn from_route(_params: &()) -> Option<Self> {
+        Some(WeddingData::default())
+    }
 
     fn user_view(&self, player: &UserUuid) -> Self::View {
         let player_data = self.players.get(player);
@@ -127,6 +169,7 @@ impl SharedLiveState for WeddingData {
                 connected_users: self
                     .players
                     .iter()
+                    .filter(|(uuid, _)| self.online.contains(uuid))
                     .map(|(_, name)| name.0.clone())
                     .collect(),
                 high_scores: self.high_scores.clone(),
@@ -161,7 +204,11 @@ impl SharedLiveState for WeddingData {
         }
     }
 
-    fn process_remote_event(&mut self, event: Self::Event, sender: UserUuid) -> LiveEffect {
+    fn process_remote_event(
+        &mut self,
+        event: Self::Event,
+        sender: UserUuid,
+    ) -> Result<LiveEffect, WeddingRejection> {
         match event {
             WeddingEvent::SetName(new_name) => {
                 self.hosts.remove(&sender); // Making sure we are not a host anymore.
@@ -169,7 +216,12 @@ impl SharedLiveState for WeddingData {
 
                 // SetName also decides what kind of participant you are.
                 if new_name == "host" {
-                    self.hosts.insert(sender);
+                    self.hosts.insert(sender.clone());
+                    // There is no sign that you are host, except for the controls.
+                    return Ok(LiveEffect::Notify(
+                        Audience::User(sender),
+                        Notification::new(NotificationLevel::Info, "Du moderierst jetzt das Quiz."),
+                    ));
                 } else if new_name == "projector" {
                     self.projectors.insert(sender);
                 } else if let Some(p) = self.players.get_mut(&sender) {
@@ -185,13 +237,14 @@ impl SharedLiveState for WeddingData {
             }
             WeddingEvent::SetGuess(new_guess) => {
                 // Get current question to check if it is still open
-                if let Some(question) = self.current_question {
-                    let question = &mut self.questions[question];
-                    if !question.state.can_guess() {
-                        return LiveEffect::None;
-                    }
-                    question.set_guess(sender, new_guess);
+                let question = self
+                    .current_question
+                    .ok_or(WeddingRejection::NoOpenQuestion)?;
+                let question = &mut self.questions[question];
+                if !question.state.can_guess() {
+                    return Err(WeddingRejection::VotingClosed);
                 }
+                question.set_guess(sender, new_guess);
             }
             WeddingEvent::SetQuestion(id) => {
                 self.current_question = id;
@@ -202,31 +255,105 @@ impl SharedLiveState for WeddingData {
                 );
             }
             WeddingEvent::SetQuestionState(id, question_state) => {
-                if let Some(question) = self.questions.get_mut(id) {
-                    question.state = question_state;
-                    self.scores = score_guesses(&self.questions);
-                    self.high_scores = high_scores(&self.players, &self.scores);
-                    self.current_question_high_scores = current_question_high_scores(
-                        self.current_question,
-                        &self.players,
-                        &self.questions,
-                    );
+                if id >= self.questions.len() {
+                    return Err(WeddingRejection::UnknownQuestion);
+                }
+                self.set_question_state(id, question_state);
+                // The host decided, so a running countdown is obsolete.
+                return Ok(LiveEffect::CancelTimer(close_voting_timer(id)));
+            }
+            WeddingEvent::CloseVotingLater(id) => {
+                let question = self
+                    .questions
+                    .get(id)
+                    .ok_or(WeddingRejection::UnknownQuestion)?;
+                if !question.state.can_guess() {
+                    return Err(WeddingRejection::VotingClosed);
+                }
+                // Remind everyone who has not voted yet.
+                let undecided = self
+                    .players
+                    .keys()
+                    .filter(|uuid| !question.guesses.contains_key(*uuid))
+                    .cloned()
+                    .collect();
+                let text = format!(
+                    "Noch {} Sekunden, dann wird die Abstimmung geschlossen!",
+                    VOTING_COUNTDOWN.as_secs()
+                );
+                return Ok(LiveEffect::Batch(vec![
+                    LiveEffect::schedule_once(
+                        close_voting_timer(id),
+                        VOTING_COUNTDOWN,
+                        WeddingTimer::CloseVoting(id),
+                    ),
+                    LiveEffect::Notify(
+                        Audience::Users(undecided),
+                        Notification::new(NotificationLevel::Warning, text),
+                    ),
+                ]));
+            }
+        }
+        Ok(LiveEffect::None)
+    }
+
+    fn process_timer(&mut self, payload: WeddingTimer) -> LiveEffect {
+        match payload {
+            WeddingTimer::CloseVoting(id) => {
+                if let Some(question) = self.questions.get(id) {
+                    if question.state.can_guess() {
+                        self.set_question_state(id, QuestionState::VotingClosed);
+                        return LiveEffect::Notify(
+                            Audience::Everyone,
+                            Notification::new(
+                                NotificationLevel::Info,
+                                "Die Abstimmung ist geschlossen.",
+                            ),
+                        );
+                    }
                 }
             }
         }
         LiveEffect::None
     }
 
-    fn join_user(&mut self, _player: UserUuid) -> LiveEffect {
+    fn join_user(&mut self, player: UserUuid) -> LiveEffect {
         // We only start tracking people after they've set their name.
+        // But we need to know who is online for the projector.
+        self.online.insert(player);
         LiveEffect::None
     }
 
+    fn leave_user(&mut self, player: UserUuid) -> LiveEffect {
+        self.online.remove(&player);
+        LiveEffect::None
+    }
+
+    fn leave_grace_period(&self) -> Option<Duration> {
+        Some(LEAVE_GRACE_PERIOD)
+    }
+
+    fn present_users(&self) -> Vec<UserUuid> {
+        self.online.iter().cloned().collect()
+    }
+
     fn route_id() -> &'static str {
         "wedding"
     }
 }
 
+impl WeddingData {
+    fn set_question_state(&mut self, id: usize, question_state: QuestionState) {
+        if let Some(question) = self.questions.get_mut(id) {
+            question.state = question_state;
+            self.scores = score_guesses(&self.questions);
+            self.high_scores = high_scores(&self.players, &self.scores);
+            self.current_question_high_scores =
+                current_question_high_scores(self.current_question, &self.players, &self.questions);
+        }
+    }
+}
+
 /// Each question is worth 100 points to the first question that got the right
 /// answer. The second person gets 99 points, then 98, etc.
 /// If you guess incorrectly, you get 0 points.
@@ -234,9 +361,9 @@ fn score_guesses(questions: &[Question]) -> HashMap<UserUuid, usize> {
     let mut scores: HashMap<UserUuid, usize> = HashMap::new();
     for question in questions {
         if let Some(answer) = question.state.to_espoused() {
-            // Create a copy of the user guesses and sort by the time they guessed.
+            // Create a copy of the user guesses and sort by the order they came in.
             let mut guesses = question.guesses.iter().collect::<Vec<_>>();
-            guesses.sort_by_key(|&(_, (_, time))| time);
+            guesses.sort_by_key(|&(_, (_, order))| order);
             // Iterate over the guesses and give points everyone that got it right.
             let mut points_left_to_give = 100;
             for (user, (guess, _)) in guesses {
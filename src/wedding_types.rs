@@ -1,8 +1,6 @@
 //! Helper module to work around a restriction in rust_elm_typegen.
 //! Right now, there can't be any non-exportable types in the module.
 
-use std::{collections::HashMap, time::Instant};
-
 use rust_elm_typegen::ElmExport;
 use serde::{Deserialize, Serialize};
 
@@ -87,10 +85,21 @@ pub enum WeddingEvent {
     SetGuess(Espoused), // Guests can only "guess", the host can "answer".
     SetQuestion(Option<usize>),
     SetQuestionState(usize, QuestionState),
+    CloseVotingLater(usize), // Closes voting on the question after a countdown.
 }
 
 impl ElmExport for WeddingEvent {}
 
+/// Why a `WeddingEvent` was not accepted.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeddingRejection {
+    NoOpenQuestion,
+    VotingClosed,
+    UnknownQuestion,
+}
+
+impl ElmExport for WeddingRejection {}
+
 #[derive(Serialize)]
 pub struct ProjectorView {
     pub question: Option<QuestionView>,
@@ -99,7 +108,7 @@ pub struct ProjectorView {
     pub current_question_high_scores: Vec<HighScoreEntry>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct HighScoreEntry {
     pub name: String,
     pub score: usize,
@@ -61,8 +61,28 @@ function getUuid(): string {
 
 function connect_websocket() {
     var ws = new WebSocket(`ws://0.0.0.0:8080/ws?uuid=${getUuid()}`);
+
+    /** Tell the server which live route we want to see. */
+    function navigate() {
+        ws.send(JSON.stringify({ Navigate: window.location.pathname }));
+    }
+
+    ws.onopen = navigate;
+    window.addEventListener('popstate', navigate);
     ws.onmessage = function (message) {
-        app.ports.websocketIn.send(JSON.parse(message.data));
+        let frame = JSON.parse(message.data);
+        if (frame.navigated !== undefined) {
+            // The server moved us to another route, keep the url in sync.
+            if (window.location.pathname !== frame.navigated) {
+                history.pushState(null, "", frame.navigated);
+            }
+            return;
+        }
+        if (frame.notification !== undefined && frame.notification.sound) {
+            // Elm can't play sounds, so we do this before passing it on.
+            new Audio(`/static/sounds/${frame.notification.sound}.mp3`).play();
+        }
+        app.ports.websocketIn.send(frame);
     };
     app.ports.websocketOut.subscribe(function (msg) { ws.send(JSON.stringify(msg)); });
 }
@@ -0,0 +1,67 @@
+module JsonPatchTest exposing (suite)
+
+import Expect exposing (Expectation)
+import Json.Decode as Decode
+import Json.Encode as Encode exposing (Value)
+import JsonPatch
+import LiveData exposing (LiveStateFrame(..), applyLiveStateFrame)
+import Test exposing (Test, describe, test)
+
+
+suite : Test
+suite =
+    describe "JsonPatch"
+        [ test "replaces the document root" <|
+            \_ ->
+                applyJson """[{"op":"replace","path":"","value":[1]}]""" """{"a":1}"""
+                    |> expectJson "[1]"
+        , test "appends to an array" <|
+            \_ ->
+                applyJson """[{"op":"add","path":"/cards/-","value":3}]""" """{"cards":[1,2]}"""
+                    |> expectJson """{"cards":[1,2,3]}"""
+        , test "adds inside an array" <|
+            \_ ->
+                applyJson """[{"op":"add","path":"/cards/1","value":3}]""" """{"cards":[1,2]}"""
+                    |> expectJson """{"cards":[1,3,2]}"""
+        , test "removes from an array" <|
+            \_ ->
+                applyJson """[{"op":"remove","path":"/cards/0"}]""" """{"cards":[1,2]}"""
+                    |> expectJson """{"cards":[2]}"""
+        , test "fails on missing paths" <|
+            \_ ->
+                applyJson """[{"op":"remove","path":"/cards/2"}]""" """{"cards":[1,2]}"""
+                    |> Expect.err
+        , test "patches need the version they were made for" <|
+            \_ ->
+                let
+                    current =
+                        { route = "/pomp/1", version = 3, data = Encode.object [] }
+
+                    patch base =
+                        Patch { route = "/pomp/1", base = base, version = base + 1, patch = [] }
+                in
+                Expect.all
+                    [ \_ -> applyLiveStateFrame (Just current) (patch 2) |> Expect.err
+                    , \_ -> applyLiveStateFrame (Just current) (patch 3) |> Result.map .version |> Expect.equal (Ok 4)
+                    , \_ -> applyLiveStateFrame Nothing (patch 3) |> Expect.err
+                    ]
+                    ()
+        ]
+
+
+{-| Decodes the patch like it comes from the server and applies it.
+-}
+applyJson : String -> String -> Result String Value
+applyJson patch document =
+    case ( Decode.decodeString JsonPatch.decodePatch patch, Decode.decodeString Decode.value document ) of
+        ( Ok operations, Ok value ) ->
+            JsonPatch.apply operations value
+
+        _ ->
+            Err "Broken test input"
+
+
+expectJson : String -> Result String Value -> Expectation
+expectJson expected result =
+    Result.map (Encode.encode 0) result
+        |> Expect.equal (Ok expected)